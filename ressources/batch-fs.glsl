@@ -0,0 +1,5 @@
+in vec2 v_uv;
+in vec3 v_color;
+out vec4 frag;
+uniform sampler2D tex;
+void main () { frag = texture(tex, v_uv) * vec4(v_color, 1.); }
\ No newline at end of file
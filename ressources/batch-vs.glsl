@@ -0,0 +1,6 @@
+in vec2 co;
+in vec2 uv;
+in vec3 color;
+out vec2 v_uv;
+out vec3 v_color;
+void main() { gl_Position = vec4(co, 0., 1.); v_uv = uv; v_color = color; }
\ No newline at end of file
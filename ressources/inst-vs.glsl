@@ -0,0 +1,10 @@
+in vec2 co;
+in vec3 color;
+in vec2 inst_offset;
+in vec3 inst_color;
+in float inst_scale;
+out vec3 v_color;
+void main() {
+  gl_Position = vec4(co * inst_scale + inst_offset, 0., 1.);
+  v_color = color * inst_color;
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+in vec2 v_uv;
+out vec4 frag;
+uniform sampler2D source;
+uniform vec2 texel;
+uniform vec4 params;
+void main() {
+  float w[3] = float[](1., 2., 1.);
+  vec4 acc = vec4(0.);
+  for (int x = -1; x <= 1; x++)
+    for (int y = -1; y <= 1; y++)
+      acc += w[x + 1] * w[y + 1] * texture(source, v_uv + vec2(x, y) * texel * params.x);
+  frag = acc / 16.;
+}
\ No newline at end of file
@@ -0,0 +1,26 @@
+in vec2 v_uv;
+out vec4 frag;
+uniform sampler2D source;
+uniform vec2 texel;
+const vec3 LUMA = vec3(.299, .587, .114);
+void main() {
+  vec3 nw = texture(source, v_uv + vec2(-1., -1.) * texel).rgb;
+  vec3 ne = texture(source, v_uv + vec2(1., -1.) * texel).rgb;
+  vec3 sw = texture(source, v_uv + vec2(-1., 1.) * texel).rgb;
+  vec3 se = texture(source, v_uv + vec2(1., 1.) * texel).rgb;
+  vec3 m = texture(source, v_uv).rgb;
+  float lnw = dot(nw, LUMA), lne = dot(ne, LUMA), lsw = dot(sw, LUMA), lse = dot(se, LUMA);
+  float lm = dot(m, LUMA);
+  float lmin = min(lm, min(min(lnw, lne), min(lsw, lse)));
+  float lmax = max(lm, max(max(lnw, lne), max(lsw, lse)));
+  vec2 dir = vec2(-((lnw + lne) - (lsw + lse)), (lnw + lsw) - (lne + lse));
+  float reduce = max((lnw + lne + lsw + lse) * .03125, 1. / 128.);
+  float rcp = 1. / (min(abs(dir.x), abs(dir.y)) + reduce);
+  dir = clamp(dir * rcp, vec2(-8.), vec2(8.)) * texel;
+  vec3 a = .5 * (texture(source, v_uv + dir * (1. / 3. - .5)).rgb
+    + texture(source, v_uv + dir * (2. / 3. - .5)).rgb);
+  vec3 b = a * .5 + .25 * (texture(source, v_uv - dir * .5).rgb
+    + texture(source, v_uv + dir * .5).rgb);
+  float lb = dot(b, LUMA);
+  frag = vec4((lb < lmin || lb > lmax) ? a : b, 1.);
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+in vec2 v_uv;
+out vec4 frag;
+uniform sampler2D source;
+uniform vec4 params; // exposure, contrast, saturation, gamma
+void main() {
+  vec3 col = texture(source, v_uv).rgb * params.x;
+  col = (col - .5) * params.y + .5;
+  float luma = dot(col, vec3(.2126, .7152, .0722));
+  col = mix(vec3(luma), col, params.z);
+  frag = vec4(pow(clamp(col, 0., 1.), vec3(1. / params.w)), 1.);
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+out vec2 v_uv;
+void main() {
+  vec2 p = vec2(float((gl_VertexID & 1) << 2) - 1., float((gl_VertexID & 2) << 1) - 1.);
+  v_uv = p * .5 + .5;
+  gl_Position = vec4(p, 0., 1.);
+}
\ No newline at end of file
@@ -0,0 +1,23 @@
+in vec2 v_uv;
+in vec3 v_color;
+out vec4 frag;
+uniform sampler2D tex;
+uniform vec4 outline_color;
+uniform float outline_width;
+uniform vec4 shadow_color;
+uniform vec2 shadow_offset;
+uniform float shadow_softness;
+// the distance is in the alpha channel, 0.5 on the glyph edge
+void main () {
+  float d = texture(tex, v_uv).a;
+  float w = fwidth(d);
+  float edge = 0.5 - outline_width;
+  float fill = smoothstep(0.5 - w, 0.5 + w, d);
+  float outer = smoothstep(edge - w, edge + w, d);
+  vec4 col = vec4(mix(outline_color.rgb, v_color, fill), mix(outline_color.a * outer, 1., fill));
+  float sd = texture(tex, v_uv - shadow_offset).a;
+  float sh = smoothstep(edge - shadow_softness - w, edge + shadow_softness + w, sd) * shadow_color.a;
+  float a = col.a + sh * (1. - col.a);
+  vec3 rgb = (col.rgb * col.a + shadow_color.rgb * sh * (1. - col.a)) / max(a, 0.0001);
+  frag = vec4(rgb, a);
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+in vec3 co3;
+in vec3 color;
+out vec3 v_color;
+uniform mat4 projection;
+uniform mat4 view;
+void main() { gl_Position = projection * view * vec4(co3, 1.); v_color = color; }
\ No newline at end of file
@@ -0,0 +1,4 @@
+in vec2 co;
+in vec2 uv;
+out vec2 v_uv;
+void main() { gl_Position = vec4(co, 0., 1.); v_uv = uv; }
\ No newline at end of file
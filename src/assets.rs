@@ -0,0 +1,113 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::bindings::BindingSet;
+use crate::gamepad::{DEFAULT_DEADZONE, MAX_DEADZONE};
+use crate::geometry::GeometryDesc;
+use crate::in_utils::DemoRng;
+use crate::shaping::FontChain;
+use crate::text::bundled_fonts;
+use crate::mesh::MeshData;
+use crate::texture::{TexImage, TexOpt};
+use crate::theme::Theme;
+
+/// Data shared by every window, loaded once from the command line options:
+///
+/// `nikut [--mesh <obj|gltf|glb>] [--texture <png|jpg>]
+///        [--filter nearest|linear|trilinear] [--wrap clamp|repeat|mirror]
+///        [--geometry <ron|json>]... [--srgb on|off] [--seed <u64>]
+///        [--font <ttf|otf>]... [--theme dark|light|high-contrast|<ron>]
+///        [--bindings <ron>] [--deadzone <0-0.95>] [--bench <frames>]`
+///
+/// The `--font` ones are fallbacks after the bundled Berylium, then come the
+/// other fonts found in `ressources/`. A theme file is added to the builtin
+/// themes, the given theme is the one of the new windows. A bindings file
+/// replaces the builtin `ressources/bindings.ron`, the deadzone is the one
+/// of the gamepad sticks. With `--bench` one window runs the tess method
+/// benchmark for that many frames, reports it and the app exits.
+pub struct Assets {
+    pub mesh: MeshData,
+    pub geometries: Vec<GeometryDesc>,
+    pub image: TexImage,
+    pub tex_opt: TexOpt,
+    /// sRGB framebuffers for the windows.
+    pub srgb: bool,
+    /// Seed of the app random generator, random if not given.
+    pub seed: u64,
+    /// Text fonts, Berylium and its fallbacks.
+    pub fonts: FontChain,
+    pub themes: Vec<Theme>,
+    /// Index of the starting theme.
+    pub theme: usize,
+    /// Actions of the keys and buttons, per window.
+    pub bindings: BindingSet,
+    pub deadzone: f32,
+    /// Frames of a benchmark run instead of the demo.
+    pub bench: Option<usize>
+}
+
+impl Assets {
+    pub fn from_args() -> Result<Assets, String> {
+        let mut assets = Assets {
+            mesh: MeshData::cube(),
+            geometries: Vec::new(),
+            image: TexImage::checker(256, 8, [[255, 255, 255, 255], [40, 40, 40, 255]]),
+            tex_opt: TexOpt::default(),
+            srgb: false,
+            seed: DemoRng::random_seed(),
+            fonts: bundled_fonts(),
+            themes: Theme::builtin(),
+            theme: 0,
+            bindings: BindingSet::builtin(),
+            deadzone: DEFAULT_DEADZONE,
+            bench: None
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+            match arg.as_str() {
+                "--mesh" =>
+                    assets.mesh = MeshData::load(&value).map_err(|e| e.to_string())?,
+                "--geometry" =>
+                    assets.geometries.push(GeometryDesc::load(&value).map_err(|e| e.to_string())?),
+                "--srgb" => assets.srgb = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(format!("unknown srgb value: {}", value))
+                },
+                "--seed" =>
+                    assets.seed = value.parse().map_err(|_| format!("invalid seed: {}", value))?,
+                "--font" => {
+                    let bytes = fs::read(&value).map_err(|e| e.to_string())?;
+                    assets.fonts.push(&value, bytes)?;
+                }
+                "--theme" => assets.theme = match assets.themes.iter().position(|t| t.name == value) {
+                    Some(index) => index,
+                    None => {
+                        assets.themes.push(Theme::load(&value).map_err(|e| e.to_string())?);
+                        assets.themes.len() - 1
+                    }
+                },
+                "--bindings" =>
+                    assets.bindings = BindingSet::load(&value).map_err(|e| e.to_string())?,
+                "--deadzone" => assets.deadzone = match value.parse::<f32>() {
+                    // NaN is out of the range too
+                    Ok(deadzone) if deadzone >= 0.0 && deadzone <= MAX_DEADZONE => deadzone,
+                    _ => return Err(format!("invalid deadzone: {}, expected 0 to {}", value, MAX_DEADZONE))
+                },
+                "--bench" =>
+                    assets.bench = Some(value.parse().map_err(|_| format!("invalid frame count: {}", value))?),
+                "--texture" =>
+                    assets.image = TexImage::load(&value).map_err(|e| e.to_string())?,
+                "--filter" =>
+                    assets.tex_opt.filter = TexOpt::parse_filter(&value).map_err(|e| e.to_string())?,
+                "--wrap" =>
+                    assets.tex_opt.wrap = TexOpt::parse_wrap(&value).map_err(|e| e.to_string())?,
+                _ => return Err(format!("unknown option: {}", arg))
+            }
+        }
+        if Path::new("ressources").is_dir() { assets.fonts.load_dir("ressources")?; }
+        Ok(assets)
+    }
+}
@@ -0,0 +1,295 @@
+use luminance::context::GraphicsContext;
+use luminance::framebuffer::{ColorSlot, DepthSlot, Framebuffer};
+use luminance::pipeline::{BoundTexture, Builder, PipelineState};
+use luminance::pixel::NormUnsigned;
+use luminance::shader::program::{Program, Uniform};
+use luminance::tess::{Mode, TessBuilder, TessSliceIndex};
+use luminance::texture::{Dim2, Flat};
+use luminance_derive::UniformInterface;
+use std::ops::Range;
+
+use crate::in_utils::{BatchVertex, Semantics};
+use crate::material::{BlendMode, Material, Scissor};
+use crate::shapes::ShapeBuilder;
+use crate::stream::RingTess;
+use crate::texture::{RgbaTex, TexFilter, TexImage, TexOpt};
+
+const BATCH_VS: &'static str = include_str!("../ressources/batch-vs.glsl");
+const BATCH_FS: &'static str = include_str!("../ressources/batch-fs.glsl");
+
+const INITIAL_CAPACITY: usize = 4096;
+
+/// Index of a texture registered in the renderer, 0 is a white texel used
+/// for the untextured shapes.
+pub type TexId = usize;
+
+/// Index of a program registered in the renderer, 0 is the default one.
+pub type ProgId = usize;
+
+/// Index of the style parameters registered in the renderer, 0 being the
+/// default ones (no outline, no shadow).
+pub type StyleId = usize;
+
+pub const WHITE_TEX: TexId = 0;
+
+/// Uniforms of the batch programs: the texture, and the style parameters
+/// used by the SDF text program (the default program ignores them).
+#[derive(Debug, UniformInterface)]
+pub struct BatchIface {
+    pub tex: Uniform<&'static BoundTexture<'static, Flat, Dim2, NormUnsigned>>,
+    #[uniform(unbound)]
+    pub outline_color: Uniform<[f32; 4]>,
+    #[uniform(unbound)]
+    pub outline_width: Uniform<f32>,
+    #[uniform(unbound)]
+    pub shadow_color: Uniform<[f32; 4]>,
+    #[uniform(unbound)]
+    pub shadow_offset: Uniform<[f32; 2]>,
+    #[uniform(unbound)]
+    pub shadow_softness: Uniform<f32>
+}
+
+/// Values of the style uniforms, the colours as written to the framebuffer.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct StyleParams {
+    pub outline_color: [f32; 4],
+    pub outline_width: f32,
+    pub shadow_color: [f32; 4],
+    pub shadow_offset: [f32; 2],
+    pub shadow_softness: f32
+}
+
+/// Sort key of a submission. The layer comes first so the painter order
+/// between layers is kept, the batches are built inside a layer: the opaque
+/// submissions between two blended ones are grouped by state, the blended
+/// ones stay where they were submitted. `clip` is the scissor of the
+/// submission, in framebuffer pixels.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct BatchKey {
+    pub layer: u16,
+    pub program: ProgId,
+    pub texture: TexId,
+    pub blend: BlendMode,
+    pub clip: Option<Scissor>,
+    pub style: StyleId
+}
+
+impl Default for BatchKey {
+    fn default() -> Self {
+        BatchKey { layer: 0, program: 0, texture: WHITE_TEX, blend: BlendMode::Opaque, clip: None, style: 0 }
+    }
+}
+
+impl BatchKey {
+    fn material(&self) -> Material {
+        Material::overlay(self.blend).scissor(self.clip)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BatchStats {
+    pub submissions: usize,
+    pub draw_calls: usize,
+    pub vertices: usize
+}
+
+struct Run {
+    key: BatchKey,
+    range: Range<usize>
+}
+
+/// Collect the quads and shapes of a frame, then draw them with one call per
+/// (program, texture, blend) run.
+pub struct BatchRenderer {
+    programs: Vec<Program<Semantics, (), BatchIface>>,
+    textures: Vec<RgbaTex>,
+    styles: Vec<StyleParams>,
+    ring: RingTess,
+    capacity: usize,
+    pending: Vec<(BatchKey, Vec<BatchVertex>)>,
+    runs: Vec<Run>,
+    stats: BatchStats
+}
+
+fn ring<C>(ctx: &mut C, capacity: usize) -> RingTess
+where C: GraphicsContext {
+    let empty = vec![BatchVertex::new([0.0, 0.0], [0.0, 0.0], [0.0, 0.0, 0.0]); capacity];
+    RingTess::new(3, || TessBuilder::new(ctx)
+        .add_vertices(&empty[..])
+        .set_mode(Mode::Triangle)
+        .build()
+        .unwrap())
+}
+
+impl BatchRenderer {
+    pub fn new<C>(ctx: &mut C) -> Self
+    where C: GraphicsContext {
+        let program = Program::<Semantics, (), BatchIface>::from_strings(None, BATCH_VS, None, BATCH_FS)
+            .expect("batch program creation")
+            .ignore_warnings();
+        let white = TexImage { size: [1, 1], texels: vec![255; 4] };
+        let opt = TexOpt { filter: TexFilter::Nearest, ..TexOpt::default() };
+        BatchRenderer {
+            programs: vec![program],
+            textures: vec![white.upload(ctx, &opt, false).expect("batch white texture")],
+            styles: vec![StyleParams::default()],
+            ring: ring(ctx, INITIAL_CAPACITY),
+            capacity: INITIAL_CAPACITY,
+            pending: Vec::new(),
+            runs: Vec::new(),
+            stats: BatchStats::default()
+        }
+    }
+
+    /// Register a program using the batch vertex layout (co, uv, color) and
+    /// the `BatchIface` uniforms.
+    pub fn add_program(&mut self, program: Program<Semantics, (), BatchIface>) -> ProgId {
+        self.programs.push(program);
+        self.programs.len() - 1
+    }
+
+    pub fn add_style(&mut self, style: StyleParams) -> StyleId {
+        self.styles.push(style);
+        self.styles.len() - 1
+    }
+
+    pub fn add_texture(&mut self, texture: RgbaTex) -> TexId {
+        self.textures.push(texture);
+        self.textures.len() - 1
+    }
+
+    pub fn texture(&self, id: TexId) -> &RgbaTex { &self.textures[id] }
+
+    pub fn stats(&self) -> BatchStats { self.stats }
+
+    /// Queue a submission, dropped if its material is invalid.
+    fn submit(&mut self, key: BatchKey, verts: Vec<BatchVertex>) {
+        match key.material().checked() {
+            Ok(_) => self.pending.push((key, verts)),
+            Err(e) => eprintln!("batch submission dropped: {:?}", e)
+        }
+    }
+
+    /// Axis aligned quad in NDC, `uvs` being the (min, max) texture corners.
+    pub fn quad(&mut self, key: BatchKey, pos: [f32; 2], size: [f32; 2], uvs: [[f32; 2]; 2], rgb: [f32; 3]) {
+        let [x, y] = pos;
+        let [w, h] = size;
+        let [[u0, v0], [u1, v1]] = uvs;
+        let (a, b, c, d) = (
+            BatchVertex::new([x, y], [u0, v0], rgb),
+            BatchVertex::new([x + w, y], [u1, v0], rgb),
+            BatchVertex::new([x + w, y + h], [u1, v1], rgb),
+            BatchVertex::new([x, y + h], [u0, v1], rgb)
+        );
+        self.submit(key, vec![a, b, c, a, c, d]);
+    }
+
+    pub fn shape(&mut self, key: BatchKey, shape: ShapeBuilder) {
+        if shape.is_empty() { return; }
+        let (verts, inds) = shape.build();
+        let verts = inds.iter().map(|i| {
+            let v = &verts[*i as usize];
+            BatchVertex::new(v.pos(), [0.0, 0.0], v.rgb())
+        }).collect();
+        self.submit(key, verts);
+    }
+
+    /// Triangle list already in the batch layout, for the backends doing
+    /// their own tessellation.
+    pub fn triangles(&mut self, key: BatchKey, verts: Vec<BatchVertex>) {
+        if !verts.is_empty() { self.submit(key, verts); }
+    }
+
+    /// Sort the frame submissions and upload them, the ctx must be current.
+    /// The submissions clipped out of a `fb_size` target are dropped.
+    pub fn prepare<C>(&mut self, ctx: &mut C, fb_size: [u32; 2])
+    where C: GraphicsContext {
+        self.pending.retain(|(key, _)| match key.material().fits(fb_size) {
+            Ok(()) => true,
+            Err(e) => { eprintln!("batch submission dropped: {:?}", e); false }
+        });
+        // painter's order within a layer, only the opaque draws between two
+        // blended ones are grouped by state
+        self.pending.sort_by_key(|(key, _)| key.layer);
+        let mut start = 0;
+        while start < self.pending.len() {
+            let layer = self.pending[start].0.layer;
+            let end = self.pending[start..].iter()
+                .position(|(key, _)| key.layer != layer || key.blend.is_transparent())
+                .map_or(self.pending.len(), |p| start + p);
+            self.pending[start..end].sort_by_key(|(key, _)| *key);
+            start = if end == start { start + 1 } else { end };
+        }
+        let nb_verts: usize = self.pending.iter().map(|(_, verts)| verts.len()).sum();
+        if nb_verts > self.capacity {
+            self.capacity = nb_verts.next_power_of_two();
+            self.ring = ring(ctx, self.capacity);
+        }
+        self.runs.clear();
+        let mut cursor = 0;
+        for (key, verts) in &self.pending {
+            let end = cursor + verts.len();
+            match self.runs.last_mut() {
+                Some(run) if run.key == *key => run.range.end = end,
+                _ => self.runs.push(Run { key: *key, range: cursor..end })
+            }
+            cursor = end;
+        }
+        let pending = &self.pending;
+        let res = self.ring.next_mut().update(|slots: &mut [BatchVertex]| {
+            let mut cursor = 0;
+            for (_, verts) in pending {
+                slots[cursor..cursor + verts.len()].copy_from_slice(verts);
+                cursor += verts.len();
+            }
+        });
+        if let Err(e) = res { eprintln!("error with the batch upload: {:?}", e); }
+        self.stats = BatchStats {
+            submissions: self.pending.len(),
+            draw_calls: self.runs.len(),
+            vertices: nb_verts
+        };
+        self.pending.clear();
+    }
+
+    /// Draw the prepared runs over the content of `fb`, which must have the
+    /// size given to `prepare`.
+    pub fn render<C, CS, DS>(&self, builder: &Builder<C>, fb: &Framebuffer<Flat, Dim2, CS, DS>)
+    where C: GraphicsContext, CS: ColorSlot<Flat, Dim2>, DS: DepthSlot<Flat, Dim2> {
+        if self.runs.is_empty() { return; }
+        let tess = self.ring.current();
+        let pipe_state = PipelineState::default().enable_clear_color(false);
+        builder.pipeline(fb, &pipe_state, |pipeline, mut shd_gate| {
+            let mut start = 0;
+            while start < self.runs.len() {
+                let program = self.runs[start].key.program;
+                let end = self.runs[start..].iter()
+                    .position(|run| run.key.program != program)
+                    .map_or(self.runs.len(), |p| start + p);
+                shd_gate.shade(&self.programs[program], |iface, mut rdr_gate| {
+                    // bound when first used, kept for the next runs on it
+                    let mut bound = None;
+                    for run in &self.runs[start..end] {
+                        let texture = run.key.texture;
+                        if bound.as_ref().map(|(id, _)| *id) != Some(texture) {
+                            bound = Some((texture, self.textures[texture].bind(&pipeline)));
+                        }
+                        if let Some((_, tex)) = &bound { iface.tex.update(tex); }
+                        let style = &self.styles[run.key.style];
+                        iface.outline_color.update(style.outline_color);
+                        iface.outline_width.update(style.outline_width);
+                        iface.shadow_color.update(style.shadow_color);
+                        iface.shadow_offset.update(style.shadow_offset);
+                        iface.shadow_softness.update(style.shadow_softness);
+                        run.key.material().scoped(|state| {
+                            rdr_gate.render(&state, |mut tess_gate| {
+                                tess_gate.render(tess.slice(run.range.clone()));
+                            });
+                        });
+                    }
+                });
+                start = end;
+            }
+        });
+    }
+}
@@ -0,0 +1,74 @@
+use gl;
+use luminance::context::GraphicsContext;
+use luminance::pipeline::PipelineState;
+use luminance::render_state::RenderState;
+use std::time::Instant;
+
+use crate::WinData;
+use crate::in_utils::{DemoMode, INSTANCE_GRID, TessMethod};
+use crate::winger::WinSurface;
+
+const MODES: [DemoMode; 6] = [
+    DemoMode::Flat(TessMethod::Direct),
+    DemoMode::Flat(TessMethod::Indexed),
+    DemoMode::Flat(TessMethod::DirectDeinter),
+    DemoMode::Flat(TessMethod::IndexedDeinter),
+    DemoMode::Instanced,
+    DemoMode::IndexedInstanced
+];
+
+#[derive(Debug)]
+pub struct BenchResult {
+    pub mode: DemoMode,
+    pub draw_calls: usize,
+    pub triangles: usize,
+    pub avg_ms: f64
+}
+
+/// Draw the same amount of triangles with every flat tess method: the
+/// instanced ones in one call, the others with one call per instance. The
+/// buffers aren't swapped so the vsync doesn't get in the way.
+pub fn run(surface: &mut WinSurface, win_data: &WinData, frames: usize) -> Vec<BenchResult> {
+    let nb_inst = (INSTANCE_GRID * INSTANCE_GRID) as usize;
+    let back_buffer = surface.back_buffer().unwrap();
+    MODES.iter().filter_map(|mode| {
+        let (program, tess) = win_data.flat_draw(*mode)?;
+        let draw_calls = match mode {
+            DemoMode::Instanced | DemoMode::IndexedInstanced => 1,
+            _ => nb_inst
+        };
+        unsafe { gl::Finish(); }
+        let start = Instant::now();
+        for _ in 0..frames {
+            surface.pipeline_builder().pipeline(
+                &back_buffer,
+                &PipelineState::default(),
+                |_, mut shd_gate| {
+                    shd_gate.shade(program, |_, mut rdr_gate| {
+                        rdr_gate.render(&RenderState::default(), |mut tess_gate| {
+                            for _ in 0..draw_calls { tess_gate.render(tess); }
+                        });
+                    });
+                }
+            );
+        }
+        unsafe { gl::Finish(); }
+        let elapsed = start.elapsed();
+        Some(BenchResult {
+            mode: *mode,
+            draw_calls,
+            triangles: nb_inst * 2,
+            avg_ms: elapsed.as_secs_f64() * 1000.0 / frames.max(1) as f64
+        })
+    }).collect()
+}
+
+pub fn report(results: &[BenchResult]) {
+    println!("{:<22} {:>10} {:>10} {:>10}", "mode", "draws", "tris", "ms/frame");
+    for res in results {
+        println!(
+            "{:<22} {:>10} {:>10} {:>10.3}",
+            format!("{:?}", res.mode), res.draw_calls, res.triangles, res.avg_ms
+        );
+    }
+}
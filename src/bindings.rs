@@ -0,0 +1,529 @@
+use glutin::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::gamepad::{PadAxis, PadButton, PadEvent};
+
+const BUILTIN: &str = include_str!("../ressources/bindings.ron");
+
+/// Stick position pressing its side, in the deadzoned range.
+const AXIS_PRESS: f32 = 0.5;
+
+#[derive(Debug)]
+pub enum BindingError {
+    IoError(std::io::Error),
+    RonError(ron::de::Error),
+    ParseError(String)
+}
+
+impl fmt::Display for BindingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            BindingError::IoError(ref e) => write!(f, "Bindings file error: {}", e),
+            BindingError::RonError(ref e) => write!(f, "Bindings RON error: {}", e),
+            BindingError::ParseError(ref e) => write!(f, "Binding error: {}", e)
+        }
+    }
+}
+
+impl From<std::io::Error> for BindingError {
+    fn from(e: std::io::Error) -> Self { BindingError::IoError(e) }
+}
+
+impl From<ron::de::Error> for BindingError {
+    fn from(e: ron::de::Error) -> Self { BindingError::RonError(e) }
+}
+
+/// What the window handlers do, by their snake case name in the files.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Close,
+    NextMode,
+    Toggle3d,
+    ToggleProjection,
+    ToggleCamera,
+    NextCull,
+    NextPost,
+    TogglePreview,
+    Bench,
+    ToggleAnimate,
+    NextTheme,
+    NextThemeAll,
+    ToggleUi,
+    Copy,
+    Paste,
+    RandomBackground,
+    /// Bind the next combo to the last action of the window.
+    Rebind
+}
+
+/// Modifier keys held.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Mods {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub logo: bool
+}
+
+impl Mods {
+    /// Follow a key, false if it isn't a modifier.
+    fn update(&mut self, key: VirtualKeyCode, down: bool) -> bool {
+        let flag = match key {
+            VirtualKeyCode::LControl | VirtualKeyCode::RControl => &mut self.ctrl,
+            VirtualKeyCode::LShift | VirtualKeyCode::RShift => &mut self.shift,
+            VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => &mut self.alt,
+            VirtualKeyCode::LWin | VirtualKeyCode::RWin => &mut self.logo,
+            _ => return false
+        };
+        *flag = down;
+        true
+    }
+}
+
+/// A key, a mouse button, a gamepad button or a side of a gamepad axis.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Trigger {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    Pad(PadButton),
+    /// The positive side if true.
+    PadAxis(PadAxis, bool)
+}
+
+const LETTERS: [VirtualKeyCode; 26] = {
+    use VirtualKeyCode::*;
+    [A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z]
+};
+
+const DIGITS: [VirtualKeyCode; 10] = {
+    use VirtualKeyCode::*;
+    [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9]
+};
+
+const FUNCTIONS: [VirtualKeyCode; 12] = {
+    use VirtualKeyCode::*;
+    [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12]
+};
+
+const NAMED_KEYS: [(&str, VirtualKeyCode); 25] = [
+    ("escape", VirtualKeyCode::Escape),
+    ("space", VirtualKeyCode::Space),
+    ("tab", VirtualKeyCode::Tab),
+    ("enter", VirtualKeyCode::Return),
+    ("backspace", VirtualKeyCode::Back),
+    ("delete", VirtualKeyCode::Delete),
+    ("insert", VirtualKeyCode::Insert),
+    ("home", VirtualKeyCode::Home),
+    ("end", VirtualKeyCode::End),
+    ("pageup", VirtualKeyCode::PageUp),
+    ("pagedown", VirtualKeyCode::PageDown),
+    ("left", VirtualKeyCode::Left),
+    ("right", VirtualKeyCode::Right),
+    ("up", VirtualKeyCode::Up),
+    ("down", VirtualKeyCode::Down),
+    ("minus", VirtualKeyCode::Minus),
+    ("equals", VirtualKeyCode::Equals),
+    ("comma", VirtualKeyCode::Comma),
+    ("period", VirtualKeyCode::Period),
+    ("slash", VirtualKeyCode::Slash),
+    ("backslash", VirtualKeyCode::Backslash),
+    ("semicolon", VirtualKeyCode::Semicolon),
+    ("apostrophe", VirtualKeyCode::Apostrophe),
+    ("lbracket", VirtualKeyCode::LBracket),
+    ("rbracket", VirtualKeyCode::RBracket)
+];
+
+impl FromStr for Trigger {
+    type Err = BindingError;
+
+    /// A key name (`a`, `5`, `f1`, `escape`...), `mouse-left`, `mouse-right`,
+    /// `mouse-middle` and `mouse-<n>`, or a pad button (`pad-south`,
+    /// `pad-start`, `pad-up`...) and an axis side (`pad-left-x+`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || BindingError::ParseError(format!("unknown key or button: {}", s));
+        if s.starts_with("pad-") {
+            let name = &s[4..];
+            if let Some(button) = PadButton::ALL.iter().find(|b| b.name() == name) {
+                return Ok(Trigger::Pad(*button));
+            }
+            let (axis, positive) = match name.len().checked_sub(1).map(|end| name.split_at(end)) {
+                Some((axis, "+")) => (axis, true),
+                Some((axis, "-")) => (axis, false),
+                _ => return Err(err())
+            };
+            return PadAxis::ALL.iter()
+                .find(|a| a.name() == axis)
+                .map(|a| Trigger::PadAxis(*a, positive))
+                .ok_or_else(err);
+        }
+        if s.starts_with("mouse-") {
+            return Ok(Trigger::Mouse(match &s[6..] {
+                "left" => MouseButton::Left,
+                "right" => MouseButton::Right,
+                "middle" => MouseButton::Middle,
+                n => MouseButton::Other(n.parse().map_err(|_| err())?)
+            }));
+        }
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c.is_ascii_lowercase() { return Ok(Trigger::Key(LETTERS[(c as u8 - b'a') as usize])); }
+            if c.is_ascii_digit() { return Ok(Trigger::Key(DIGITS[(c as u8 - b'0') as usize])); }
+        }
+        if let Some(n) = s.get(1..).filter(|_| s.starts_with('f')).and_then(|n| n.parse::<usize>().ok()) {
+            return FUNCTIONS.get(n.wrapping_sub(1)).map(|key| Trigger::Key(*key)).ok_or_else(err);
+        }
+        NAMED_KEYS.iter().find(|(name, _)| *name == s).map(|(_, key)| Trigger::Key(*key)).ok_or_else(err)
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let key = match *self {
+            Trigger::Mouse(MouseButton::Left) => return write!(f, "mouse-left"),
+            Trigger::Mouse(MouseButton::Right) => return write!(f, "mouse-right"),
+            Trigger::Mouse(MouseButton::Middle) => return write!(f, "mouse-middle"),
+            Trigger::Mouse(MouseButton::Other(n)) => return write!(f, "mouse-{}", n),
+            Trigger::Pad(button) => return write!(f, "pad-{}", button.name()),
+            Trigger::PadAxis(axis, positive) =>
+                return write!(f, "pad-{}{}", axis.name(), if positive { "+" } else { "-" }),
+            Trigger::Key(key) => key
+        };
+        if let Some(i) = LETTERS.iter().position(|k| *k == key) { return write!(f, "{}", (b'a' + i as u8) as char); }
+        if let Some(i) = DIGITS.iter().position(|k| *k == key) { return write!(f, "{}", i); }
+        if let Some(i) = FUNCTIONS.iter().position(|k| *k == key) { return write!(f, "f{}", i + 1); }
+        match NAMED_KEYS.iter().find(|(_, k)| *k == key) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => write!(f, "{:?}", key)
+        }
+    }
+}
+
+/// Modifiers with keys or buttons pressed together, written `ctrl+shift+s`,
+/// `mouse-right`, `pad-start` or `a+s` for a chord. The bindings without a modifier
+/// need none held.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Binding {
+    pub mods: Mods,
+    /// Sorted by their names, a chord being the same in any order.
+    pub triggers: Vec<Trigger>
+}
+
+impl Binding {
+    pub fn new(mods: Mods, mut triggers: Vec<Trigger>) -> Self {
+        triggers.sort_by_key(|t| t.to_string());
+        triggers.dedup();
+        Binding { mods, triggers }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = BindingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mods = Mods::default();
+        let mut triggers = Vec::new();
+        for part in s.split('+').map(|part| part.trim().to_lowercase()) {
+            match part.as_str() {
+                "ctrl" | "control" => mods.ctrl = true,
+                "shift" => mods.shift = true,
+                "alt" => mods.alt = true,
+                "logo" | "super" | "cmd" => mods.logo = true,
+                name => triggers.push(name.parse()?)
+            }
+        }
+        if triggers.is_empty() { return Err(BindingError::ParseError(format!("no key in {}", s))); }
+        Ok(Binding::new(mods, triggers))
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let Mods { ctrl, shift, alt, logo } = self.mods;
+        let mods = [(ctrl, "ctrl"), (shift, "shift"), (alt, "alt"), (logo, "logo")];
+        let mut parts: Vec<String> = mods.iter().filter(|(on, _)| *on).map(|(_, name)| name.to_string()).collect();
+        parts.extend(self.triggers.iter().map(|t| t.to_string()));
+        write!(f, "{}", parts.join("+"))
+    }
+}
+
+impl<'de> Deserialize<'de> for Binding {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// The actions of the bindings, `any` binding the keys bound to nothing.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Bindings {
+    #[serde(default)]
+    map: HashMap<Binding, Action>,
+    #[serde(default)]
+    any: Option<Action>
+}
+
+impl Bindings {
+    /// Bind, returning the action the binding had.
+    pub fn bind(&mut self, binding: Binding, action: Action) -> Option<Action> {
+        self.map.insert(binding, action)
+    }
+
+    /// Remove all the bindings of `action`.
+    pub fn unbind_action(&mut self, action: Action) { self.map.retain(|_, a| *a != action); }
+
+    pub fn get(&self, binding: &Binding) -> Option<Action> { self.map.get(binding).cloned() }
+
+    pub fn bindings_of(&self, action: Action) -> Vec<&Binding> {
+        let mut bindings: Vec<_> = self.map.iter().filter(|(_, a)| **a == action).map(|(b, _)| b).collect();
+        bindings.sort_by_key(|b| b.to_string());
+        bindings
+    }
+
+    fn merge(&mut self, other: &Bindings) {
+        self.map.extend(other.map.iter().map(|(b, a)| (b.clone(), *a)));
+        if other.any.is_some() { self.any = other.any; }
+    }
+}
+
+/// Bindings of all the windows, read from a RON file:
+///
+/// ```ron
+/// (
+///     bindings: (
+///         map: { "escape": close, "ctrl+c": copy, "a+s": next_mode },
+///         any: Some(random_background),
+///     ),
+///     windows: { 1: (map: { "mouse-right": next_theme }) },
+/// )
+/// ```
+///
+/// The `windows` ones are added to the others for the windows of these
+/// indices, in creation order.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct BindingSet {
+    bindings: Bindings,
+    #[serde(default)]
+    windows: HashMap<usize, Bindings>
+}
+
+impl BindingSet {
+    /// The bindings of `ressources/bindings.ron`, built in.
+    pub fn builtin() -> Self { ron::de::from_str(BUILTIN).expect("builtin bindings") }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<BindingSet, BindingError> {
+        let set: BindingSet = ron::de::from_str(&fs::read_to_string(path)?)?;
+        Ok(set)
+    }
+
+    /// The bindings of the window `index`.
+    pub fn window(&self, index: usize) -> Bindings {
+        let mut bindings = self.bindings.clone();
+        if let Some(own) = self.windows.get(&index) { bindings.merge(own); }
+        bindings
+    }
+}
+
+struct Held {
+    /// Modifiers at the press.
+    mods: Mods,
+    /// Part of a chord that fired, nothing more at the release.
+    used: bool
+}
+
+/// Turn the key and button events of a window into its actions. The single
+/// key bindings fire at the release, the chords when their last key is
+/// pressed, so that the keys of a chord don't fire on their own.
+pub struct ActionMap {
+    bindings: Bindings,
+    mods: Mods,
+    held: HashMap<Trigger, Held>,
+    last: Option<Action>,
+    capture: Option<Action>
+}
+
+impl ActionMap {
+    pub fn new(bindings: Bindings) -> Self {
+        ActionMap { bindings, mods: Mods::default(), held: HashMap::new(), last: None, capture: None }
+    }
+
+    pub fn bindings(&self) -> &Bindings { &self.bindings }
+
+    /// Last action fired, `Rebind` aside.
+    pub fn last(&self) -> Option<Action> { self.last }
+
+    /// The next combo released replaces the bindings of `action`.
+    pub fn capture(&mut self, action: Action) { self.capture = Some(action); }
+
+    /// Follow an event, returning the action it fires. To give every event,
+    /// even the ones a UI takes, with the action dropped.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> Option<Action> {
+        let (trigger, down) = match event {
+            WindowEvent::KeyboardInput { input: KeyboardInput { state, virtual_keycode: Some(key), .. }, .. } =>
+                return self.key(*key, *state == ElementState::Pressed),
+            WindowEvent::MouseInput { state, button, .. } => (Trigger::Mouse(*button), *state == ElementState::Pressed),
+            WindowEvent::Focused(false) => {
+                self.mods = Mods::default();
+                self.held.clear();
+                return None;
+            }
+            _ => return None
+        };
+        self.trigger(trigger, down)
+    }
+
+    /// The modifiers only change the held ones, they fire nothing.
+    fn key(&mut self, key: VirtualKeyCode, down: bool) -> Option<Action> {
+        if self.mods.update(key, down) { return None; }
+        self.trigger(Trigger::Key(key), down)
+    }
+
+    /// Follow a gamepad event, the axis sides pressing past the half. The
+    /// pads are told apart by nothing, they act like a shared keyboard.
+    pub fn handle_pad(&mut self, event: &PadEvent) -> Option<Action> {
+        match *event {
+            PadEvent::Button { button, pressed, .. } => self.trigger(Trigger::Pad(button), pressed),
+            PadEvent::Axis { axis, value, .. } => {
+                let mut action = None;
+                for &positive in &[false, true] {
+                    let side = Trigger::PadAxis(axis, positive);
+                    let on = if positive { value >= AXIS_PRESS } else { value <= -AXIS_PRESS };
+                    if on != self.held.contains_key(&side) { action = self.trigger(side, on).or(action); }
+                }
+                action
+            }
+            _ => None
+        }
+    }
+
+    fn trigger(&mut self, trigger: Trigger, down: bool) -> Option<Action> {
+        let action = if down { self.press(trigger) } else { self.release(trigger) };
+        if action.is_some() && action != Some(Action::Rebind) { self.last = action; }
+        action
+    }
+
+    fn press(&mut self, trigger: Trigger) -> Option<Action> {
+        // the key repeats
+        if self.held.contains_key(&trigger) { return None; }
+        self.held.insert(trigger, Held { mods: self.mods, used: false });
+        if self.held.len() < 2 || self.capture.is_some() { return None; }
+        let chord = Binding::new(self.mods, self.held.keys().cloned().collect());
+        let action = self.bindings.get(&chord)?;
+        for held in self.held.values_mut() { held.used = true; }
+        Some(action)
+    }
+
+    fn release(&mut self, trigger: Trigger) -> Option<Action> {
+        let held = self.held.get(&trigger)?;
+        if let Some(action) = self.capture.take() {
+            let binding = Binding::new(held.mods, self.held.keys().cloned().collect());
+            for held in self.held.values_mut() { held.used = true; }
+            self.held.remove(&trigger);
+            self.bindings.unbind_action(action);
+            self.bindings.bind(binding, action);
+            return None;
+        }
+        let held = self.held.remove(&trigger)?;
+        if held.used { return None; }
+        let binding = Binding::new(held.mods, vec![trigger]);
+        match (self.bindings.get(&binding), trigger) {
+            (Some(action), _) => Some(action),
+            (None, Trigger::Key(_)) => self.bindings.any,
+            (None, _) => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_of(bindings: &[(&str, Action)], any: Option<Action>) -> ActionMap {
+        let mut map = Bindings { any, ..Bindings::default() };
+        for (binding, action) in bindings { map.bind(binding.parse().unwrap(), *action); }
+        ActionMap::new(map)
+    }
+
+    /// The actions of key presses and releases, `true` for a press.
+    fn keys(map: &mut ActionMap, keys: &[(VirtualKeyCode, bool)]) -> Vec<Action> {
+        keys.iter().filter_map(|(key, down)| map.key(*key, *down)).collect()
+    }
+
+    #[test]
+    fn ctrl_v_pastes_only() {
+        use VirtualKeyCode::{LControl, V};
+        let mut map = map_of(&[("ctrl+v", Action::Paste)], Some(Action::RandomBackground));
+        let fired = keys(&mut map, &[(LControl, true), (V, true), (V, false), (LControl, false)]);
+        assert_eq!(fired, vec![Action::Paste]);
+        // the modifier released first, the paste still goes with the ctrl of the press
+        let fired = keys(&mut map, &[(LControl, true), (V, true), (LControl, false), (V, false)]);
+        assert_eq!(fired, vec![Action::Paste]);
+        // unbound, v alone falls back to `any`
+        assert_eq!(keys(&mut map, &[(V, true), (V, false)]), vec![Action::RandomBackground]);
+    }
+
+    #[test]
+    fn binding_round_trip() {
+        let binding: Binding = "ctrl+c".parse().unwrap();
+        assert!(binding.mods.ctrl && !binding.mods.shift);
+        assert_eq!(binding.triggers, vec![Trigger::Key(VirtualKeyCode::C)]);
+        assert_eq!(binding.to_string(), "ctrl+c");
+        assert_eq!(binding.to_string().parse::<Binding>().unwrap(), binding);
+        // a chord is the same in any order
+        assert_eq!("s+a".parse::<Binding>().unwrap().to_string(), "a+s");
+        assert!("ctrl".parse::<Binding>().is_err());
+        assert!("ctrl+nokey".parse::<Binding>().is_err());
+    }
+
+    #[test]
+    fn chord_fires_once() {
+        use VirtualKeyCode::{A, S};
+        let mut map = map_of(&[("a+s", Action::NextMode), ("a", Action::Copy), ("s", Action::Paste)], None);
+        let fired = keys(&mut map, &[(A, true), (S, true), (S, true), (A, false), (S, false)]);
+        assert_eq!(fired, vec![Action::NextMode]);
+        // in the other order too
+        let fired = keys(&mut map, &[(S, true), (A, true), (A, false), (S, false)]);
+        assert_eq!(fired, vec![Action::NextMode]);
+        // alone, the keys have their own bindings
+        assert_eq!(keys(&mut map, &[(A, true), (A, false)]), vec![Action::Copy]);
+        assert_eq!(keys(&mut map, &[(S, true), (S, false)]), vec![Action::Paste]);
+    }
+
+    #[test]
+    fn rebind_replaces() {
+        use VirtualKeyCode::{LControl, B, N, X};
+        let mut map = map_of(&[("n", Action::NextMode), ("ctrl+n", Action::NextMode), ("x", Action::NextPost)], None);
+        assert_eq!(keys(&mut map, &[(N, true), (N, false)]), vec![Action::NextMode]);
+        assert_eq!(map.last(), Some(Action::NextMode));
+        map.capture(Action::NextMode);
+        // the captured combo fires nothing
+        assert_eq!(keys(&mut map, &[(LControl, true), (B, true), (B, false), (LControl, false)]), vec![]);
+        let now: Vec<_> = map.bindings().bindings_of(Action::NextMode).iter().map(|b| b.to_string()).collect();
+        assert_eq!(now, vec!["ctrl+b"]);
+        assert_eq!(keys(&mut map, &[(N, true), (N, false)]), vec![]);
+        assert_eq!(keys(&mut map, &[(LControl, true), (B, true), (B, false), (LControl, false)]), vec![Action::NextMode]);
+        // the other actions keep theirs
+        assert_eq!(keys(&mut map, &[(X, true), (X, false)]), vec![Action::NextPost]);
+    }
+
+    #[test]
+    fn pad_axis_sides() {
+        let mut map = map_of(&[("pad-left-x+", Action::NextMode), ("pad-left-x-", Action::NextPost)], None);
+        let mut axis = |value| map.handle_pad(&PadEvent::Axis { id: 0, axis: PadAxis::LeftX, value });
+        // the side presses past the half and fires at its release
+        assert_eq!(axis(0.7), None);
+        assert_eq!(axis(0.9), None);
+        assert_eq!(axis(0.2), Some(Action::NextMode));
+        assert_eq!(axis(-0.6), None);
+        // straight to the other side, releasing the first one
+        assert_eq!(axis(0.8), Some(Action::NextPost));
+        assert_eq!(axis(0.0), Some(Action::NextMode));
+    }
+}
@@ -0,0 +1,232 @@
+use cgmath::{
+    ortho, perspective, Deg, InnerSpace, Matrix4, Point3, Rad, Vector3
+};
+use glutin::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta,
+    VirtualKeyCode, WindowEvent
+};
+use luminance_derive::UniformInterface;
+use luminance::shader::program::Uniform;
+
+pub type M44 = [[f32; 4]; 4];
+
+#[derive(Debug, UniformInterface)]
+pub struct CamIface {
+    #[uniform(unbound)]
+    pub projection: Uniform<M44>,
+    #[uniform(unbound)]
+    pub view: Uniform<M44>
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Projection {
+    Perspective { fovy: f32, near: f32, far: f32 },
+    Orthographic { height: f32, near: f32, far: f32 }
+}
+
+impl Projection {
+    pub fn perspective() -> Self {
+        Projection::Perspective { fovy: 60.0, near: 0.1, far: 100.0 }
+    }
+
+    pub fn orthographic() -> Self {
+        Projection::Orthographic { height: 4.0, near: -100.0, far: 100.0 }
+    }
+
+    pub fn toggle(&self) -> Self {
+        match *self {
+            Projection::Perspective {..} => Projection::orthographic(),
+            Projection::Orthographic {..} => Projection::perspective()
+        }
+    }
+
+    pub fn matrix(&self, aspect: f32) -> Matrix4<f32> {
+        match *self {
+            Projection::Perspective { fovy, near, far } =>
+                perspective(Deg(fovy), aspect, near, far),
+            Projection::Orthographic { height, near, far } => {
+                let (hw, hh) = (height * aspect / 2.0, height / 2.0);
+                ortho(-hw, hw, -hh, hh, near, far)
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CamMode { Orbit, Fly }
+
+pub struct Camera {
+    pub projection: Projection,
+    mode: CamMode,
+    target: Point3<f32>,
+    eye: Point3<f32>,
+    yaw: f32,
+    pitch: f32,
+    dist: f32,
+    aspect: f32,
+    cursor: Option<(f64, f64)>,
+    dragging: bool,
+    moves: [bool; 6]
+}
+
+const ORBIT_SPEED: f32 = 0.01;
+const ZOOM_SPEED: f32 = 0.2;
+const FLY_SPEED: f32 = 2.0;
+/// Turn of a gamepad stick pushed all the way, in cursor pixels per second.
+const PAD_TURN: f32 = 300.0;
+
+impl Camera {
+    pub fn new(aspect: f32) -> Self {
+        let mut cam = Camera {
+            projection: Projection::perspective(),
+            mode: CamMode::Orbit,
+            target: Point3::new(0.0, 0.0, 0.0),
+            eye: Point3::new(0.0, 0.0, 3.0),
+            yaw: 0.0,
+            pitch: 0.3,
+            dist: 3.0,
+            aspect,
+            cursor: None,
+            dragging: false,
+            moves: [false; 6]
+        };
+        cam.place_orbit();
+        cam
+    }
+
+    pub fn mode(&self) -> CamMode { self.mode }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            CamMode::Orbit => CamMode::Fly,
+            CamMode::Fly => {
+                self.dist = (self.eye - self.target).magnitude().max(0.5);
+                self.place_orbit();
+                CamMode::Orbit
+            }
+        };
+        self.moves = [false; 6];
+    }
+
+    pub fn set_aspect(&mut self, w: u32, h: u32) {
+        if h > 0 { self.aspect = w as f32 / h as f32; }
+    }
+
+    pub fn projection_matrix(&self) -> M44 { self.projection.matrix(self.aspect).into() }
+
+    pub fn view_matrix(&self) -> M44 {
+        let target = match self.mode {
+            CamMode::Orbit => self.target,
+            CamMode::Fly => self.eye + self.forward()
+        };
+        Matrix4::look_at(self.eye, target, Vector3::unit_y()).into()
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.mode == CamMode::Fly && self.moves.iter().any(|m| *m)
+    }
+
+    /// Feed a window event to the active controller, returns true if the
+    /// event was consumed (the view then needs a redraw).
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state, button, .. } => {
+                let btn = match self.mode {
+                    CamMode::Orbit => MouseButton::Left,
+                    CamMode::Fly => MouseButton::Right
+                };
+                if *button != btn { return false; }
+                self.dragging = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let (x, y) = (position.x, position.y);
+                let res = match self.cursor {
+                    Some((ox, oy)) if self.dragging => {
+                        self.rotate((x - ox) as f32, (y - oy) as f32);
+                        true
+                    }
+                    _ => false
+                };
+                self.cursor = Some((x, y));
+                res
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.dragging = false;
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let dy = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 20.0
+                };
+                match self.mode {
+                    CamMode::Orbit => {
+                        self.dist = (self.dist - dy * ZOOM_SPEED).max(0.5);
+                        self.place_orbit();
+                    }
+                    CamMode::Fly => self.eye += self.forward() * dy * ZOOM_SPEED
+                }
+                true
+            }
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput { state, virtual_keycode: Some(key), .. },
+                ..
+            } if self.mode == CamMode::Fly => {
+                let idx = match key {
+                    VirtualKeyCode::W => 0,
+                    VirtualKeyCode::S => 1,
+                    VirtualKeyCode::A => 2,
+                    VirtualKeyCode::D => 3,
+                    VirtualKeyCode::E => 4,
+                    VirtualKeyCode::Q => 5,
+                    _ => return false
+                };
+                self.moves[idx] = *state == ElementState::Pressed;
+                true
+            }
+            _ => false
+        }
+    }
+
+    /// Turn like a cursor drag with a gamepad stick at `x` and `y`, from -1
+    /// to 1 and up being positive, held for `dt` seconds.
+    pub fn turn(&mut self, x: f32, y: f32, dt: f32) { self.rotate(x * PAD_TURN * dt, -y * PAD_TURN * dt); }
+
+    /// Advance the fly controller by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        if !self.is_moving() { return; }
+        let fwd = self.forward();
+        let right = fwd.cross(Vector3::unit_y()).normalize();
+        let axis = |p: bool, n: bool| (p as i8 - n as i8) as f32;
+        let step = fwd * axis(self.moves[0], self.moves[1])
+            + right * axis(self.moves[3], self.moves[2])
+            + Vector3::unit_y() * axis(self.moves[4], self.moves[5]);
+        if step.magnitude2() > 0.0 { self.eye += step.normalize() * FLY_SPEED * dt; }
+    }
+
+    fn forward(&self) -> Vector3<f32> {
+        match self.mode {
+            CamMode::Orbit => (self.target - self.eye).normalize(),
+            CamMode::Fly => {
+                let (sy, cy) = self.yaw.sin_cos();
+                let (sp, cp) = self.pitch.sin_cos();
+                Vector3::new(-sy * cp, -sp, -cy * cp)
+            }
+        }
+    }
+
+    fn rotate(&mut self, dx: f32, dy: f32) {
+        let limit = Rad::from(Deg(89.0)).0;
+        self.yaw -= dx * ORBIT_SPEED;
+        self.pitch = (self.pitch + dy * ORBIT_SPEED).max(-limit).min(limit);
+        if self.mode == CamMode::Orbit { self.place_orbit(); }
+    }
+
+    fn place_orbit(&mut self) {
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        self.eye = self.target + Vector3::new(sy * cp, sp, cy * cp) * self.dist;
+    }
+}
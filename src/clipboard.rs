@@ -0,0 +1,239 @@
+use gl;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::texture::TexImage;
+
+#[derive(Debug, PartialEq)]
+pub enum ClipError {
+    /// Nothing of the asked kind in the clipboard.
+    Empty,
+    BackendError(String),
+    SizeError { size: [u32; 2], len: usize }
+}
+
+impl fmt::Display for ClipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ClipError::Empty => write!(f, "Clipboard empty"),
+            ClipError::BackendError(ref e) => write!(f, "Clipboard error: {}", e),
+            ClipError::SizeError { size, len } =>
+                write!(f, "Clipboard image error: {} bytes for {}x{}", len, size[0], size[1])
+        }
+    }
+}
+
+/// RGBA8 image, the first row being the top one as in the clipboards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClipImage {
+    pub size: [u32; 2],
+    pub rgba: Vec<u8>
+}
+
+impl ClipImage {
+    /// Bytes of an image of `size`, none if it doesn't fit in memory.
+    fn byte_len(size: [u32; 2]) -> Option<usize> {
+        (size[0] as usize).checked_mul(size[1] as usize)?.checked_mul(4)
+    }
+
+    pub fn new(size: [u32; 2], rgba: Vec<u8>) -> Result<Self, ClipError> {
+        if ClipImage::byte_len(size) != Some(rgba.len()) {
+            return Err(ClipError::SizeError { size, len: rgba.len() });
+        }
+        Ok(ClipImage { size, rgba })
+    }
+
+    /// Rows in the other order, from or to the bottom first OpenGL order.
+    fn flipped(size: [u32; 2], rows: &[u8]) -> Vec<u8> {
+        let stride = size[0] as usize * 4;
+        rows.chunks(stride.max(1)).rev().flatten().cloned().collect()
+    }
+
+    /// From pixels read with `glReadPixels`, bottom row first.
+    pub fn from_gl(size: [u32; 2], rgba: &[u8]) -> Result<Self, ClipError> {
+        ClipImage::new(size, ClipImage::flipped(size, rgba))
+    }
+
+    /// Read the color buffer of the current ctx framebuffer, after the
+    /// frame is drawn and before the swap.
+    pub fn read_gl(size: [u32; 2]) -> Result<Self, ClipError> {
+        let len = ClipImage::byte_len(size).ok_or(ClipError::SizeError { size, len: 0 })?;
+        let mut rgba = vec![0u8; len];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0, 0, size[0] as i32, size[1] as i32,
+                gl::RGBA, gl::UNSIGNED_BYTE, rgba.as_mut_ptr() as *mut _
+            );
+        }
+        ClipImage::from_gl(size, &rgba)
+    }
+
+    pub fn to_tex_image(&self) -> TexImage {
+        TexImage { size: self.size, texels: ClipImage::flipped(self.size, &self.rgba) }
+    }
+}
+
+/// Where the clipboard content lives.
+pub trait ClipboardBackend {
+    fn get_text(&mut self) -> Result<String, ClipError>;
+    fn set_text(&mut self, text: &str) -> Result<(), ClipError>;
+    fn get_image(&mut self) -> Result<ClipImage, ClipError>;
+    fn set_image(&mut self, image: &ClipImage) -> Result<(), ClipError>;
+}
+
+/// Clipboard of the process only, for the headless runs and the platforms
+/// without a system one. Setting a kind of content clears the other, like
+/// the system clipboards.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryClipboard {
+    text: Option<String>,
+    image: Option<ClipImage>
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn get_text(&mut self) -> Result<String, ClipError> { self.text.clone().ok_or(ClipError::Empty) }
+
+    fn set_text(&mut self, text: &str) -> Result<(), ClipError> {
+        self.text = Some(text.to_string());
+        self.image = None;
+        Ok(())
+    }
+
+    fn get_image(&mut self) -> Result<ClipImage, ClipError> { self.image.clone().ok_or(ClipError::Empty) }
+
+    fn set_image(&mut self, image: &ClipImage) -> Result<(), ClipError> {
+        self.image = Some(image.clone());
+        self.text = None;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "system-clipboard")]
+pub struct SystemClipboard {
+    inner: arboard::Clipboard
+}
+
+#[cfg(feature = "system-clipboard")]
+impl SystemClipboard {
+    pub fn new() -> Result<Self, ClipError> {
+        let inner = arboard::Clipboard::new().map_err(|e| ClipError::BackendError(e.to_string()))?;
+        Ok(SystemClipboard { inner })
+    }
+}
+
+#[cfg(feature = "system-clipboard")]
+fn system_error(e: arboard::Error) -> ClipError {
+    match e {
+        arboard::Error::ContentNotAvailable => ClipError::Empty,
+        e => ClipError::BackendError(e.to_string())
+    }
+}
+
+#[cfg(feature = "system-clipboard")]
+impl ClipboardBackend for SystemClipboard {
+    fn get_text(&mut self) -> Result<String, ClipError> { self.inner.get_text().map_err(system_error) }
+
+    fn set_text(&mut self, text: &str) -> Result<(), ClipError> {
+        self.inner.set_text(text.to_string()).map_err(system_error)
+    }
+
+    fn get_image(&mut self) -> Result<ClipImage, ClipError> {
+        let image = self.inner.get_image().map_err(system_error)?;
+        ClipImage::new([image.width as u32, image.height as u32], image.bytes.into_owned())
+    }
+
+    fn set_image(&mut self, image: &ClipImage) -> Result<(), ClipError> {
+        self.inner.set_image(arboard::ImageData {
+            width: image.size[0] as usize,
+            height: image.size[1] as usize,
+            bytes: image.rgba.as_slice().into()
+        }).map_err(system_error)
+    }
+}
+
+/// Clipboard service shared by the window handlers, cheap to clone.
+#[derive(Clone)]
+pub struct Clipboard {
+    backend: Rc<RefCell<Box<dyn ClipboardBackend>>>
+}
+
+impl Clipboard {
+    pub fn new<B: ClipboardBackend + 'static>(backend: B) -> Self {
+        Clipboard { backend: Rc::new(RefCell::new(Box::new(backend))) }
+    }
+
+    pub fn memory() -> Self { Clipboard::new(MemoryClipboard::default()) }
+
+    /// The system clipboard, the memory one if there is none.
+    pub fn system() -> Self {
+        #[cfg(feature = "system-clipboard")]
+        {
+            match SystemClipboard::new() {
+                Ok(system) => return Clipboard::new(system),
+                Err(e) => eprintln!("{}, using a memory clipboard", e)
+            }
+        }
+        Clipboard::memory()
+    }
+
+    pub fn get_text(&self) -> Result<String, ClipError> { self.backend.borrow_mut().get_text() }
+
+    pub fn set_text(&self, text: &str) -> Result<(), ClipError> { self.backend.borrow_mut().set_text(text) }
+
+    pub fn get_image(&self) -> Result<ClipImage, ClipError> { self.backend.borrow_mut().get_image() }
+
+    pub fn set_image(&self, image: &ClipImage) -> Result<(), ClipError> {
+        self.backend.borrow_mut().set_image(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1x2 image, a red row over a blue one.
+    fn red_over_blue() -> ClipImage {
+        ClipImage::new([1, 2], vec![255, 0, 0, 255, 0, 0, 255, 255]).unwrap()
+    }
+
+    #[test]
+    fn memory_text_and_image() {
+        let clipboard = Clipboard::memory();
+        assert_eq!(clipboard.get_text(), Err(ClipError::Empty));
+        assert_eq!(clipboard.get_image(), Err(ClipError::Empty));
+        clipboard.set_text("nikut").unwrap();
+        assert_eq!(clipboard.get_text(), Ok("nikut".to_string()));
+        clipboard.set_image(&red_over_blue()).unwrap();
+        assert_eq!(clipboard.get_image(), Ok(red_over_blue()));
+    }
+
+    #[test]
+    fn memory_kinds_clear_each_other() {
+        let clipboard = Clipboard::memory();
+        clipboard.set_text("nikut").unwrap();
+        clipboard.set_image(&red_over_blue()).unwrap();
+        assert_eq!(clipboard.get_text(), Err(ClipError::Empty));
+        clipboard.set_text("nikut").unwrap();
+        assert_eq!(clipboard.get_image(), Err(ClipError::Empty));
+    }
+
+    #[test]
+    fn image_size_errors() {
+        assert_eq!(ClipImage::new([2, 2], vec![0; 15]), Err(ClipError::SizeError { size: [2, 2], len: 15 }));
+        assert_eq!(ClipImage::new([2, 2], vec![0; 17]), Err(ClipError::SizeError { size: [2, 2], len: 17 }));
+        // the size in u32 would overflow to 0
+        let huge = [1 << 16, 1 << 16];
+        assert_eq!(ClipImage::new(huge, Vec::new()), Err(ClipError::SizeError { size: huge, len: 0 }));
+        assert!(ClipImage::new([0, 0], Vec::new()).is_ok());
+    }
+
+    #[test]
+    fn gl_rows_flip() {
+        let gl_rows = [0, 0, 255, 255, 255, 0, 0, 255];
+        let image = ClipImage::from_gl([1, 2], &gl_rows).unwrap();
+        assert_eq!(image, red_over_blue());
+        assert_eq!(image.to_tex_image().texels, gl_rows.to_vec());
+    }
+}
@@ -0,0 +1,279 @@
+use serde::de::{self, Deserialize, Deserializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// RGBA colour with sRGB encoded components in [0, 1], as they are written in
+/// hex codes or picked in an image editor. The GPU side wants linear values
+/// when the framebuffer is sRGB, see `clear_color` and `vertex_rgb`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ColorError {
+    HexError(String),
+    UnknownName(String)
+}
+
+impl fmt::Display for ColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ColorError::HexError(ref e) => write!(f, "Invalid hex colour: {}", e),
+            ColorError::UnknownName(ref e) => write!(f, "Unknown colour name: {}", e)
+        }
+    }
+}
+
+/// How the colours of a palette are picked around the base hue.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Harmony {
+    /// Neighbour hues, 30° apart.
+    Analogous,
+    Complementary,
+    Triadic,
+    Tetradic
+}
+
+const NAMED: [(&str, u32); 20] = [
+    ("black", 0x000000), ("white", 0xffffff), ("gray", 0x808080), ("silver", 0xc0c0c0),
+    ("red", 0xff0000), ("green", 0x008000), ("lime", 0x00ff00), ("blue", 0x0000ff),
+    ("yellow", 0xffff00), ("cyan", 0x00ffff), ("magenta", 0xff00ff), ("orange", 0xffa500),
+    ("purple", 0x800080), ("pink", 0xffc0cb), ("brown", 0xa52a2a), ("navy", 0x000080),
+    ("teal", 0x008080), ("olive", 0x808000), ("maroon", 0x800000), ("coral", 0xff7f50)
+];
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn to_u8(c: f32) -> u8 { (c.max(0.0).min(1.0) * 255.0).round() as u8 }
+
+impl Color {
+    pub const BLACK: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+    pub const WHITE: Color = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+
+    pub fn rgb(r: f32, g: f32, b: f32) -> Self { Color { r, g, b, a: 1.0 } }
+
+    pub fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self { Color { r, g, b, a } }
+
+    pub fn from_rgb8(rgb: [u8; 3]) -> Self {
+        Color::rgb(rgb[0] as f32 / 255.0, rgb[1] as f32 / 255.0, rgb[2] as f32 / 255.0)
+    }
+
+    pub fn to_rgb8(&self) -> [u8; 3] { [to_u8(self.r), to_u8(self.g), to_u8(self.b)] }
+
+    pub fn with_alpha(mut self, a: f32) -> Self { self.a = a; self }
+
+    pub fn from_linear(rgba: [f32; 4]) -> Self {
+        Color::rgba(linear_to_srgb(rgba[0]), linear_to_srgb(rgba[1]), linear_to_srgb(rgba[2]), rgba[3])
+    }
+
+    pub fn to_linear(&self) -> [f32; 4] {
+        [srgb_to_linear(self.r), srgb_to_linear(self.g), srgb_to_linear(self.b), self.a]
+    }
+
+    /// `#rgb`, `#rrggbb` or `#rrggbbaa`, the `#` being optional.
+    pub fn from_hex(hex: &str) -> Result<Self, ColorError> {
+        let digits = hex.trim_start_matches('#');
+        let err = || ColorError::HexError(hex.to_string());
+        // from_str_radix takes a sign
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) { return Err(err()); }
+        let value = u32::from_str_radix(digits, 16).map_err(|_| err())?;
+        let byte = |shift: u32| ((value >> shift) & 0xff) as f32 / 255.0;
+        match digits.len() {
+            3 => {
+                let nibble = |shift: u32| ((value >> shift) & 0xf) as f32 / 15.0;
+                Ok(Color::rgb(nibble(8), nibble(4), nibble(0)))
+            }
+            6 => Ok(Color::rgb(byte(16), byte(8), byte(0))),
+            8 => Ok(Color::rgba(byte(24), byte(16), byte(8), byte(0))),
+            _ => Err(err())
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        let [r, g, b] = self.to_rgb8();
+        if self.a < 1.0 { format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, to_u8(self.a)) }
+        else { format!("#{:02x}{:02x}{:02x}", r, g, b) }
+    }
+
+    /// One of the basic CSS colour names.
+    pub fn named(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        NAMED.iter().find(|(n, _)| *n == name).map(|(_, hex)| Color::rgb(
+            ((hex >> 16) & 0xff) as f32 / 255.0,
+            ((hex >> 8) & 0xff) as f32 / 255.0,
+            (hex & 0xff) as f32 / 255.0
+        ))
+    }
+
+    /// Hue in degrees, saturation and value in [0, 1].
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x)
+        };
+        let m = v - c;
+        Color::rgb(r + m, g + m, b + m)
+    }
+
+    fn hue_chroma(&self) -> (f32, f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let c = max - min;
+        let h = if c == 0.0 { 0.0 }
+            else if max == self.r { 60.0 * ((self.g - self.b) / c).rem_euclid(6.0) }
+            else if max == self.g { 60.0 * ((self.b - self.r) / c + 2.0) }
+            else { 60.0 * ((self.r - self.g) / c + 4.0) };
+        (h, c, max, min)
+    }
+
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let (h, c, max, _) = self.hue_chroma();
+        (h, if max == 0.0 { 0.0 } else { c / max }, max)
+    }
+
+    /// Hue in degrees, saturation and lightness in [0, 1].
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> Self {
+        let v = l + s * l.min(1.0 - l);
+        let sv = if v == 0.0 { 0.0 } else { 2.0 * (1.0 - l / v) };
+        Color::from_hsv(h, sv, v)
+    }
+
+    pub fn to_hsl(&self) -> (f32, f32, f32) {
+        let (h, c, max, min) = self.hue_chroma();
+        let l = (max + min) / 2.0;
+        let s = if l == 0.0 || l == 1.0 { 0.0 } else { c / (1.0 - (2.0 * l - 1.0).abs()) };
+        (h, s, l)
+    }
+
+    /// Mix in linear space, `t` going from `self` (0) to `other` (1).
+    pub fn lerp(&self, other: Color, t: f32) -> Self {
+        let (a, b) = (self.to_linear(), other.to_linear());
+        let mix = |i: usize| a[i] + (b[i] - a[i]) * t;
+        Color::from_linear([mix(0), mix(1), mix(2), mix(3)])
+    }
+
+    /// `n` colours evenly spread from `self` to `other`, both included.
+    pub fn gradient(&self, other: Color, n: usize) -> Vec<Color> {
+        match n {
+            0 => Vec::new(),
+            1 => vec![*self],
+            _ => (0..n).map(|i| self.lerp(other, i as f32 / (n - 1) as f32)).collect()
+        }
+    }
+
+    /// The base colour followed by its harmonic hues, saturation and value kept.
+    pub fn palette(&self, harmony: Harmony) -> Vec<Color> {
+        let (h, s, v) = self.to_hsv();
+        let offsets: &[f32] = match harmony {
+            Harmony::Analogous => &[0.0, -30.0, 30.0],
+            Harmony::Complementary => &[0.0, 180.0],
+            Harmony::Triadic => &[0.0, 120.0, 240.0],
+            Harmony::Tetradic => &[0.0, 90.0, 180.0, 270.0]
+        };
+        offsets.iter().map(|o| Color::from_hsv(h + o, s, v).with_alpha(self.a)).collect()
+    }
+
+    /// Value for a clear colour, linear for an sRGB framebuffer since gl then
+    /// encodes what it writes.
+    pub fn clear_color(&self, srgb_fb: bool) -> [f32; 4] {
+        if srgb_fb { self.to_linear() } else { [self.r, self.g, self.b, self.a] }
+    }
+
+    /// Vertex colour of sRGB channels for the framebuffer, see `vertex_rgb`.
+    pub fn fb_rgb(rgb: [f32; 3], srgb_fb: bool) -> [f32; 3] {
+        Color::rgb(rgb[0], rgb[1], rgb[2]).vertex_rgb(srgb_fb)
+    }
+
+    /// Value for a `VertexColor`, see `clear_color`. The colours are floats
+    /// so the dark linear values keep their precision.
+    pub fn vertex_rgb(&self, srgb_fb: bool) -> [f32; 3] {
+        let [r, g, b, _] = self.clear_color(srgb_fb);
+        [r, g, b]
+    }
+}
+
+impl From<[u8; 3]> for Color {
+    fn from(rgb: [u8; 3]) -> Self { Color::from_rgb8(rgb) }
+}
+
+impl FromStr for Color {
+    type Err = ColorError;
+
+    /// A hex code or a colour name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('#') { Color::from_hex(s) }
+        else { Color::named(s).ok_or_else(|| ColorError::UnknownName(s.to_string())) }
+    }
+}
+
+/// Read from a hex code or a colour name, as in the theme files.
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Color, b: Color) {
+        let d = [a.r - b.r, a.g - b.g, a.b - b.b, a.a - b.a];
+        assert!(d.iter().all(|d| d.abs() < 1e-4), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        for hex in &["#000000", "#ffffff", "#ff7f50", "#12345678"] {
+            assert_eq!(Color::from_hex(hex).unwrap().to_hex(), *hex);
+        }
+        assert_eq!(Color::from_hex("abc").unwrap().to_hex(), "#aabbcc");
+        for bad in &["#ggg", "#12", "#+fffff", "#1234567"] {
+            assert_eq!(Color::from_hex(bad), Err(ColorError::HexError(bad.to_string())));
+        }
+    }
+
+    #[test]
+    fn hsv_hsl_round_trip() {
+        assert_close(Color::from_hsv(120.0, 1.0, 1.0), Color::named("lime").unwrap());
+        assert_close(Color::from_hsl(0.0, 1.0, 0.5), Color::named("red").unwrap());
+        assert_close(Color::from_hsl(60.0, 1.0, 0.5), Color::named("yellow").unwrap());
+        for (name, _) in NAMED.iter() {
+            let color = Color::named(name).unwrap();
+            let (h, s, v) = color.to_hsv();
+            assert_close(Color::from_hsv(h, s, v), color);
+            let (h, s, l) = color.to_hsl();
+            assert_close(Color::from_hsl(h, s, l), color);
+        }
+    }
+
+    #[test]
+    fn srgb_linear() {
+        for &(srgb, linear) in &[(0.0, 0.0), (0.5, 0.214_041), (1.0, 1.0)] {
+            assert!((srgb_to_linear(srgb) - linear).abs() < 1e-5);
+            assert!((linear_to_srgb(linear) - srgb).abs() < 1e-5);
+        }
+        // the dark values keep their precision in the vertex colours
+        let dark = Color::from_rgb8([3, 3, 3]).vertex_rgb(true);
+        assert!(dark[0] > 0.0 && dark[0] < 1.0 / 255.0);
+    }
+}
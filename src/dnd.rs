@@ -0,0 +1,195 @@
+use glutin::event::{ElementState, MouseButton, WindowEvent};
+use glutin::window::WindowId;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::color::Color;
+use crate::input::{rect_of, window_at};
+
+/// Distance in pixels the cursor moves before a press turns into a drag.
+const DRAG_THRESHOLD: f64 = 6.0;
+
+/// What is dragged.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Payload {
+    Color(Color),
+    Text(String),
+    /// Files from the OS, see `DragDrop::os_event`.
+    Files(Vec<PathBuf>)
+}
+
+/// Drag and drop events of a window, `pos` in its physical pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DndEvent {
+    Enter { payload: Payload, pos: [f64; 2] },
+    Over { payload: Payload, pos: [f64; 2] },
+    Leave,
+    Drop { payload: Payload, pos: [f64; 2] }
+}
+
+struct Drag {
+    source: WindowId,
+    payload: Payload,
+    /// Screen position of the press.
+    from: [f64; 2],
+    started: bool,
+    target: Option<WindowId>
+}
+
+/// Drags between the windows of a `WinManager`. The cursor stays with the
+/// window where the drag started, its position is moved to the screen with
+/// the window rects (`WinManager::screen_rects`) to find the window under
+/// it, which gets the hover and drop events.
+pub struct DragDrop {
+    drag: Option<Drag>,
+    cursors: HashMap<WindowId, [f64; 2]>,
+    /// Files hovering a window, from the OS.
+    os_files: HashMap<WindowId, Vec<PathBuf>>,
+    /// Files of a drop already sent, their own drop events to skip.
+    os_dropped: HashMap<WindowId, Vec<PathBuf>>
+}
+
+impl DragDrop {
+    pub fn new() -> Self {
+        DragDrop { drag: None, cursors: HashMap::new(), os_files: HashMap::new(), os_dropped: HashMap::new() }
+    }
+
+    pub fn is_dragging(&self) -> bool { self.drag.as_ref().map_or(false, |drag| drag.started) }
+
+    pub fn source(&self) -> Option<WindowId> { self.drag.as_ref().map(|drag| drag.source) }
+
+    /// Last cursor position in `window`, in its pixels.
+    pub fn cursor(&self, window: WindowId) -> Option<[f64; 2]> { self.cursors.get(&window).cloned() }
+
+    fn screen(&self, window: WindowId, rects: &[(WindowId, [f64; 4])]) -> Option<[f64; 2]> {
+        let local = self.cursor(window)?;
+        let rect = rect_of(rects, window)?;
+        Some([rect[0] + local[0], rect[1] + local[1]])
+    }
+
+    /// Arm a drag of `payload` from the cursor in `source`, it starts when
+    /// the cursor moves far enough.
+    pub fn press(&mut self, source: WindowId, payload: Payload, rects: &[(WindowId, [f64; 4])]) {
+        if let Some(from) = self.screen(source, rects) {
+            self.drag = Some(Drag { source, payload, from, started: false, target: None });
+        }
+    }
+
+    /// Feed a cursor or left button event of `window`, the events of the drag
+    /// come back with the window they are for.
+    pub fn handle_event(
+        &mut self,
+        window: WindowId,
+        event: &WindowEvent,
+        rects: &[(WindowId, [f64; 4])]
+    ) -> Vec<(WindowId, DndEvent)> {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursors.insert(window, [position.x as f64, position.y as f64]);
+                if self.source() == Some(window) { self.moved(rects) } else { Vec::new() }
+            }
+            WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. }
+            if self.source() == Some(window) => self.release(rects),
+            _ => Vec::new()
+        }
+    }
+
+    fn moved(&mut self, rects: &[(WindowId, [f64; 4])]) -> Vec<(WindowId, DndEvent)> {
+        let source = match self.source() { Some(source) => source, None => return Vec::new() };
+        let screen = match self.screen(source, rects) { Some(screen) => screen, None => return Vec::new() };
+        let drag = self.drag.as_mut().unwrap();
+        if !drag.started {
+            let (dx, dy) = (screen[0] - drag.from[0], screen[1] - drag.from[1]);
+            if dx * dx + dy * dy < DRAG_THRESHOLD * DRAG_THRESHOLD { return Vec::new(); }
+            drag.started = true;
+        }
+        let mut events = Vec::new();
+        let under = window_at(rects, screen);
+        let target = under.map(|(win, _)| win);
+        if drag.target != target {
+            if let Some(old) = drag.target { events.push((old, DndEvent::Leave)); }
+            if let Some((win, pos)) = under {
+                events.push((win, DndEvent::Enter { payload: drag.payload.clone(), pos }));
+            }
+            drag.target = target;
+        }
+        else if let Some((win, pos)) = under {
+            events.push((win, DndEvent::Over { payload: drag.payload.clone(), pos }));
+        }
+        events
+    }
+
+    fn release(&mut self, rects: &[(WindowId, [f64; 4])]) -> Vec<(WindowId, DndEvent)> {
+        let screen = self.source().and_then(|source| self.screen(source, rects));
+        match self.drag.take() {
+            Some(Drag { started: true, payload, target: Some(target), .. }) => {
+                match screen.and_then(|p| window_at(rects, p)) {
+                    Some((win, pos)) if win == target => vec![(win, DndEvent::Drop { payload, pos })],
+                    _ => vec![(target, DndEvent::Leave)]
+                }
+            }
+            _ => Vec::new()
+        }
+    }
+
+    /// Drop the current drag, the hovered window gets a leave.
+    pub fn cancel(&mut self) -> Vec<(WindowId, DndEvent)> {
+        match self.drag.take().and_then(|drag| drag.target) {
+            Some(target) => vec![(target, DndEvent::Leave)],
+            None => Vec::new()
+        }
+    }
+
+    /// Turn the OS file events of `window` into its drag and drop events. The
+    /// OS sends one event per file: the hovering payload grows with them, and
+    /// the first drop sends them all, the drops of the others being skipped.
+    /// Without hover events each file comes as its own drop.
+    pub fn os_event(&mut self, window: WindowId, event: &WindowEvent) -> Option<DndEvent> {
+        let pos = self.cursor(window).unwrap_or([0.0, 0.0]);
+        match event {
+            WindowEvent::HoveredFile(path) => {
+                self.os_dropped.remove(&window);
+                let files = self.os_files.entry(window).or_insert_with(Vec::new);
+                let first = files.is_empty();
+                files.push(path.clone());
+                let payload = Payload::Files(files.clone());
+                Some(if first { DndEvent::Enter { payload, pos } } else { DndEvent::Over { payload, pos } })
+            }
+            WindowEvent::HoveredFileCancelled => {
+                self.os_files.remove(&window);
+                Some(DndEvent::Leave)
+            }
+            WindowEvent::DroppedFile(path) => {
+                if let Some(pending) = self.os_dropped.get_mut(&window) {
+                    if let Some(index) = pending.iter().position(|p| p == path) {
+                        pending.remove(index);
+                        if pending.is_empty() { self.os_dropped.remove(&window); }
+                        return None;
+                    }
+                }
+                let files = match self.os_files.remove(&window) {
+                    Some(files) if files.contains(path) => files,
+                    _ => vec![path.clone()]
+                };
+                let rest: Vec<_> = files.iter().filter(|p| *p != path).cloned().collect();
+                if !rest.is_empty() { self.os_dropped.insert(window, rest); }
+                Some(DndEvent::Drop { payload: Payload::Files(files), pos })
+            }
+            _ => None
+        }
+    }
+
+    /// Forget a closed window, a drag from it is cancelled.
+    pub fn remove_window(&mut self, window: WindowId) -> Vec<(WindowId, DndEvent)> {
+        self.cursors.remove(&window);
+        self.os_files.remove(&window);
+        self.os_dropped.remove(&window);
+        if self.source() == Some(window) {
+            return self.cancel().into_iter().filter(|(target, _)| *target != window).collect();
+        }
+        if let Some(drag) = &mut self.drag {
+            if drag.target == Some(window) { drag.target = None; }
+        }
+        Vec::new()
+    }
+}
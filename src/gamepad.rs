@@ -0,0 +1,380 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Stick values under it read as zero.
+pub const DEFAULT_DEADZONE: f32 = 0.15;
+
+/// Past it, the sticks would barely move anything.
+pub const MAX_DEADZONE: f32 = 0.95;
+
+/// Polling period with a pad connected, and without one for the hotplug.
+const POLL_ACTIVE: Duration = Duration::from_millis(16);
+const POLL_IDLE: Duration = Duration::from_millis(500);
+
+pub type PadId = usize;
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum PadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight
+}
+
+impl PadButton {
+    pub const ALL: [PadButton; 17] = [
+        PadButton::South, PadButton::East, PadButton::North, PadButton::West,
+        PadButton::LeftBumper, PadButton::RightBumper, PadButton::LeftTrigger, PadButton::RightTrigger,
+        PadButton::Select, PadButton::Start, PadButton::Mode, PadButton::LeftThumb, PadButton::RightThumb,
+        PadButton::DPadUp, PadButton::DPadDown, PadButton::DPadLeft, PadButton::DPadRight
+    ];
+
+    /// Name in the binding files, after `pad-`.
+    pub fn name(self) -> &'static str {
+        match self {
+            PadButton::South => "south",
+            PadButton::East => "east",
+            PadButton::North => "north",
+            PadButton::West => "west",
+            PadButton::LeftBumper => "lb",
+            PadButton::RightBumper => "rb",
+            PadButton::LeftTrigger => "lt",
+            PadButton::RightTrigger => "rt",
+            PadButton::Select => "select",
+            PadButton::Start => "start",
+            PadButton::Mode => "mode",
+            PadButton::LeftThumb => "lthumb",
+            PadButton::RightThumb => "rthumb",
+            PadButton::DPadUp => "up",
+            PadButton::DPadDown => "down",
+            PadButton::DPadLeft => "left",
+            PadButton::DPadRight => "right"
+        }
+    }
+}
+
+/// Axes from -1 to 1, up and right being positive.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum PadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftZ,
+    RightZ
+}
+
+impl PadAxis {
+    pub const ALL: [PadAxis; 6] =
+        [PadAxis::LeftX, PadAxis::LeftY, PadAxis::RightX, PadAxis::RightY, PadAxis::LeftZ, PadAxis::RightZ];
+
+    /// Name in the binding files, after `pad-` and before the `+` or `-` side.
+    pub fn name(self) -> &'static str {
+        match self {
+            PadAxis::LeftX => "left-x",
+            PadAxis::LeftY => "left-y",
+            PadAxis::RightX => "right-x",
+            PadAxis::RightY => "right-y",
+            PadAxis::LeftZ => "left-z",
+            PadAxis::RightZ => "right-z"
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PadEvent {
+    Connected { id: PadId, name: String },
+    Disconnected { id: PadId },
+    Button { id: PadId, button: PadButton, pressed: bool },
+    Axis { id: PadId, axis: PadAxis, value: f32 }
+}
+
+/// Where the gamepad events come from.
+pub trait GamepadBackend {
+    /// The events since the last call, the pads already there when the
+    /// backend starts coming as connections.
+    fn poll(&mut self) -> Vec<PadEvent>;
+
+    /// True if new pads show up without the app, to poll even with none.
+    fn hotplug(&self) -> bool;
+}
+
+/// Gamepads driven by the code, to script or test the pad handling without
+/// a device. The clones share their pads and events.
+#[derive(Clone, Default)]
+pub struct VirtualGamepad {
+    queue: Rc<RefCell<VecDeque<PadEvent>>>,
+    next_id: Rc<RefCell<PadId>>
+}
+
+impl VirtualGamepad {
+    pub fn new() -> Self { VirtualGamepad::default() }
+
+    fn push(&self, event: PadEvent) { self.queue.borrow_mut().push_back(event); }
+
+    pub fn connect(&self, name: &str) -> PadId {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            *next_id += 1;
+            *next_id - 1
+        };
+        self.push(PadEvent::Connected { id, name: name.to_string() });
+        id
+    }
+
+    pub fn disconnect(&self, id: PadId) { self.push(PadEvent::Disconnected { id }); }
+
+    pub fn press(&self, id: PadId, button: PadButton) {
+        self.push(PadEvent::Button { id, button, pressed: true });
+    }
+
+    pub fn release(&self, id: PadId, button: PadButton) {
+        self.push(PadEvent::Button { id, button, pressed: false });
+    }
+
+    pub fn move_axis(&self, id: PadId, axis: PadAxis, value: f32) {
+        self.push(PadEvent::Axis { id, axis, value: value.max(-1.0).min(1.0) });
+    }
+}
+
+impl GamepadBackend for VirtualGamepad {
+    fn poll(&mut self) -> Vec<PadEvent> { self.queue.borrow_mut().drain(..).collect() }
+
+    fn hotplug(&self) -> bool { false }
+}
+
+#[cfg(feature = "gamepad")]
+pub struct SystemGamepad {
+    gilrs: gilrs::Gilrs,
+    /// The pads found at the start.
+    pending: Vec<PadEvent>
+}
+
+#[cfg(feature = "gamepad")]
+impl SystemGamepad {
+    pub fn new() -> Result<Self, String> {
+        // the deadzones are done by `Gamepads`, the same for every backend
+        let gilrs = gilrs::GilrsBuilder::new().with_default_filters(false).build().map_err(|e| e.to_string())?;
+        let pending = gilrs.gamepads()
+            .map(|(id, pad)| PadEvent::Connected { id: id.into(), name: pad.name().to_string() })
+            .collect();
+        Ok(SystemGamepad { gilrs, pending })
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn gilrs_button(button: gilrs::Button) -> Option<PadButton> {
+    use gilrs::Button;
+    Some(match button {
+        Button::South => PadButton::South,
+        Button::East => PadButton::East,
+        Button::North => PadButton::North,
+        Button::West => PadButton::West,
+        Button::LeftTrigger => PadButton::LeftBumper,
+        Button::RightTrigger => PadButton::RightBumper,
+        Button::LeftTrigger2 => PadButton::LeftTrigger,
+        Button::RightTrigger2 => PadButton::RightTrigger,
+        Button::Select => PadButton::Select,
+        Button::Start => PadButton::Start,
+        Button::Mode => PadButton::Mode,
+        Button::LeftThumb => PadButton::LeftThumb,
+        Button::RightThumb => PadButton::RightThumb,
+        Button::DPadUp => PadButton::DPadUp,
+        Button::DPadDown => PadButton::DPadDown,
+        Button::DPadLeft => PadButton::DPadLeft,
+        Button::DPadRight => PadButton::DPadRight,
+        _ => return None
+    })
+}
+
+#[cfg(feature = "gamepad")]
+fn gilrs_axis(axis: gilrs::Axis) -> Option<PadAxis> {
+    use gilrs::Axis;
+    Some(match axis {
+        Axis::LeftStickX => PadAxis::LeftX,
+        Axis::LeftStickY => PadAxis::LeftY,
+        Axis::RightStickX => PadAxis::RightX,
+        Axis::RightStickY => PadAxis::RightY,
+        Axis::LeftZ => PadAxis::LeftZ,
+        Axis::RightZ => PadAxis::RightZ,
+        _ => return None
+    })
+}
+
+#[cfg(feature = "gamepad")]
+impl GamepadBackend for SystemGamepad {
+    fn poll(&mut self) -> Vec<PadEvent> {
+        use gilrs::EventType;
+        let mut events: Vec<_> = self.pending.drain(..).collect();
+        while let Some(gilrs::Event { id: pad, event, .. }) = self.gilrs.next_event() {
+            let id: PadId = pad.into();
+            events.extend(match event {
+                EventType::Connected => {
+                    let name = self.gilrs.gamepad(pad).name().to_string();
+                    Some(PadEvent::Connected { id, name })
+                }
+                EventType::Disconnected => Some(PadEvent::Disconnected { id }),
+                EventType::ButtonPressed(button, _) =>
+                    gilrs_button(button).map(|button| PadEvent::Button { id, button, pressed: true }),
+                EventType::ButtonReleased(button, _) =>
+                    gilrs_button(button).map(|button| PadEvent::Button { id, button, pressed: false }),
+                EventType::AxisChanged(axis, value, _) =>
+                    gilrs_axis(axis).map(|axis| PadEvent::Axis { id, axis, value }),
+                _ => None
+            });
+        }
+        events
+    }
+
+    fn hotplug(&self) -> bool { true }
+}
+
+#[derive(Clone, Debug, Default)]
+struct PadState {
+    buttons: Vec<PadButton>,
+    axes: HashMap<PadAxis, f32>
+}
+
+/// The connected gamepads and their state, from a backend. The axes go
+/// through a deadzone: under it they are zero, over it they are scaled back
+/// to the whole range.
+pub struct Gamepads {
+    backend: Box<dyn GamepadBackend>,
+    pads: HashMap<PadId, PadState>,
+    deadzone: f32
+}
+
+impl Gamepads {
+    pub fn new<B: GamepadBackend + 'static>(backend: B) -> Self {
+        Gamepads { backend: Box::new(backend), pads: HashMap::new(), deadzone: DEFAULT_DEADZONE }
+    }
+
+    /// The system gamepads, the `fallback` virtual ones if there are none,
+    /// to script them through its clones.
+    pub fn system(fallback: VirtualGamepad) -> Self {
+        #[cfg(feature = "gamepad")]
+        {
+            match SystemGamepad::new() {
+                Ok(system) => return Gamepads::new(system),
+                Err(e) => eprintln!("Gamepad error: {}, using virtual gamepads", e)
+            }
+        }
+        Gamepads::new(fallback)
+    }
+
+    pub fn set_deadzone(&mut self, deadzone: f32) { self.deadzone = deadzone.max(0.0).min(MAX_DEADZONE); }
+
+    fn filter(&self, value: f32) -> f32 {
+        if value.abs() <= self.deadzone { 0.0 }
+        else { value.signum() * (value.abs() - self.deadzone) / (1.0 - self.deadzone) }
+    }
+
+    /// Read the backend and update the pads, returning the events with the
+    /// deadzones applied, the axis moves staying in them left out.
+    pub fn poll(&mut self) -> Vec<PadEvent> {
+        let mut events = Vec::new();
+        for event in self.backend.poll() {
+            match event {
+                PadEvent::Connected { id, .. } => { self.pads.insert(id, PadState::default()); }
+                PadEvent::Disconnected { id } => {
+                    if self.pads.remove(&id).is_none() { continue; }
+                }
+                PadEvent::Button { id, button, pressed } => {
+                    let pad = match self.pads.get_mut(&id) { Some(pad) => pad, None => continue };
+                    if pressed == pad.buttons.contains(&button) { continue; }
+                    if pressed { pad.buttons.push(button); } else { pad.buttons.retain(|b| *b != button); }
+                }
+                PadEvent::Axis { id, axis, value } => {
+                    let value = self.filter(value);
+                    let pad = match self.pads.get_mut(&id) { Some(pad) => pad, None => continue };
+                    let old = pad.axes.insert(axis, value).unwrap_or(0.0);
+                    if (old - value).abs() < std::f32::EPSILON { continue; }
+                    events.push(PadEvent::Axis { id, axis, value });
+                    continue;
+                }
+            }
+            events.push(event);
+        }
+        events
+    }
+
+    /// When to poll again, if the pads can change without the app.
+    pub fn poll_period(&self) -> Option<Duration> {
+        if !self.pads.is_empty() { Some(POLL_ACTIVE) }
+        else if self.backend.hotplug() { Some(POLL_IDLE) }
+        else { None }
+    }
+
+    /// Value of `axis` on the pads, the one pushed the most.
+    pub fn stick(&self, axis: PadAxis) -> f32 {
+        self.pads.values()
+            .filter_map(|pad| pad.axes.get(&axis).cloned())
+            .fold(0.0, |most, value| if value.abs() > most.abs() { value } else { most })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn virtual_pads() -> (VirtualGamepad, Gamepads) {
+        let pads = VirtualGamepad::new();
+        (pads.clone(), Gamepads::new(pads))
+    }
+
+    #[test]
+    fn connect_press_disconnect() {
+        let (pads, mut gamepads) = virtual_pads();
+        assert_eq!(gamepads.poll_period(), None);
+        let id = pads.connect("pad");
+        pads.press(id, PadButton::South);
+        // a repeated press changes nothing
+        pads.press(id, PadButton::South);
+        pads.release(id, PadButton::South);
+        assert_eq!(gamepads.poll(), vec![
+            PadEvent::Connected { id, name: "pad".to_string() },
+            PadEvent::Button { id, button: PadButton::South, pressed: true },
+            PadEvent::Button { id, button: PadButton::South, pressed: false }
+        ]);
+        assert_eq!(gamepads.poll_period(), Some(POLL_ACTIVE));
+        pads.disconnect(id);
+        // the events of an unknown pad are dropped
+        pads.press(id, PadButton::South);
+        pads.disconnect(id);
+        assert_eq!(gamepads.poll(), vec![PadEvent::Disconnected { id }]);
+        assert_eq!(gamepads.poll_period(), None);
+    }
+
+    #[test]
+    fn axis_deadzone() {
+        let (pads, mut gamepads) = virtual_pads();
+        gamepads.set_deadzone(0.25);
+        let id = pads.connect("pad");
+        gamepads.poll();
+        pads.move_axis(id, PadAxis::LeftX, 0.1);
+        assert_eq!(gamepads.poll(), vec![]);
+        assert_eq!(gamepads.stick(PadAxis::LeftX), 0.0);
+        pads.move_axis(id, PadAxis::LeftX, -0.625);
+        assert_eq!(gamepads.poll(), vec![PadEvent::Axis { id, axis: PadAxis::LeftX, value: -0.5 }]);
+        pads.move_axis(id, PadAxis::LeftX, 2.0);
+        gamepads.poll();
+        assert_eq!(gamepads.stick(PadAxis::LeftX), 1.0);
+        pads.move_axis(id, PadAxis::LeftX, 0.15);
+        assert_eq!(gamepads.poll(), vec![PadEvent::Axis { id, axis: PadAxis::LeftX, value: 0.0 }]);
+    }
+}
@@ -0,0 +1,421 @@
+use luminance::context::GraphicsContext;
+use luminance::tess::{Mode, Tess, TessBuilder, TessError};
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::color::Color;
+use crate::in_utils::{Colors, Positions, TessMethod, TexVertex, Vertex};
+use crate::mesh::{MeshData, MeshError};
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+pub enum AttrType { F32x2, F32x3, U8x3 }
+
+impl AttrType {
+    fn size(&self) -> usize {
+        match self { AttrType::F32x2 => 2, AttrType::F32x3 | AttrType::U8x3 => 3 }
+    }
+}
+
+/// One vertex attribute, `name` being the shader input it feeds.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct AttrDesc {
+    pub name: String,
+    pub ty: AttrType,
+    #[serde(default)]
+    pub normalized: bool
+}
+
+/// The vertex values, as numbers whatever the attribute type: one row per
+/// vertex with every attribute in a row, or one column per attribute.
+#[derive(Clone, Debug, Deserialize)]
+pub enum VertexData {
+    Interleaved(Vec<Vec<f32>>),
+    Deinterleaved(Vec<Vec<f32>>)
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+pub enum GeoMode { Point, Line, LineStrip, Triangle, TriangleFan, TriangleStrip }
+
+impl Default for GeoMode {
+    fn default() -> Self { GeoMode::Triangle }
+}
+
+impl GeoMode {
+    fn mode(&self) -> Mode {
+        match self {
+            GeoMode::Point => Mode::Point,
+            GeoMode::Line => Mode::Line,
+            GeoMode::LineStrip => Mode::LineStrip,
+            GeoMode::Triangle => Mode::Triangle,
+            GeoMode::TriangleFan => Mode::TriangleFan,
+            GeoMode::TriangleStrip => Mode::TriangleStrip
+        }
+    }
+}
+
+/// Vertex layout and geometry read from a RON or JSON file, for example:
+///
+/// ```ron
+/// (
+///     attributes: [(name: "co", ty: F32x2), (name: "color", ty: U8x3, normalized: true)],
+///     data: Interleaved([[0.0, 0.5, 255, 0, 0], [-0.5, -0.5, 0, 255, 0], [0.5, -0.5, 0, 0, 255]]),
+///     indices: None,
+///     mode: Triangle
+/// )
+/// ```
+///
+/// The layout must match one of the vertex types the shaders are compiled
+/// for, see `GeoKind`, in any attribute order. `color` is given as normalized
+/// `U8x3` or as `F32x3` in [0, 1], the other inputs as floats only. The
+/// textured layout is interleaved only.
+#[derive(Clone, Debug, Deserialize)]
+pub struct GeometryDesc {
+    pub attributes: Vec<AttrDesc>,
+    pub data: VertexData,
+    #[serde(default)]
+    pub indices: Option<Vec<u32>>,
+    #[serde(default)]
+    pub mode: GeoMode
+}
+
+/// The program family a geometry is drawn with.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GeoKind {
+    /// `co` and `color`, the flat program.
+    Flat,
+    /// `co` and `uv`, the textured program.
+    Textured,
+    /// `co3` with any of `normal`, `uv` and `color`, the 3D program.
+    Mesh
+}
+
+pub struct Geometry {
+    pub kind: GeoKind,
+    pub tess: Tess
+}
+
+#[derive(Debug)]
+pub enum GeometryError {
+    IoError(std::io::Error),
+    RonError(ron::de::Error),
+    JsonError(serde_json::Error),
+    LayoutError(String),
+    DataError(String),
+    TessError(TessError)
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            GeometryError::IoError(ref e) => write!(f, "Geometry file error: {}", e),
+            GeometryError::RonError(ref e) => write!(f, "Geometry RON error: {}", e),
+            GeometryError::JsonError(ref e) => write!(f, "Geometry JSON error: {}", e),
+            GeometryError::LayoutError(ref e) => write!(f, "Geometry layout error: {}", e),
+            GeometryError::DataError(ref e) => write!(f, "Geometry data error: {}", e),
+            GeometryError::TessError(ref e) => write!(f, "Geometry tess creation error: {:?}", e)
+        }
+    }
+}
+
+impl From<std::io::Error> for GeometryError {
+    fn from(e: std::io::Error) -> Self { GeometryError::IoError(e) }
+}
+
+impl From<ron::de::Error> for GeometryError {
+    fn from(e: ron::de::Error) -> Self { GeometryError::RonError(e) }
+}
+
+impl From<serde_json::Error> for GeometryError {
+    fn from(e: serde_json::Error) -> Self { GeometryError::JsonError(e) }
+}
+
+impl From<TessError> for GeometryError {
+    fn from(e: TessError) -> Self { GeometryError::TessError(e) }
+}
+
+impl From<MeshError> for GeometryError {
+    fn from(e: MeshError) -> Self {
+        match e {
+            MeshError::TessError(e) => GeometryError::TessError(e),
+            e => GeometryError::DataError(e.to_string())
+        }
+    }
+}
+
+/// Shader inputs a file can use, with the types and normalizations they
+/// can be given in. The shaders get floats, the normalized bytes divided by 255.
+const KNOWN_ATTRS: [(&str, &[(AttrType, bool)]); 5] = [
+    ("co", &[(AttrType::F32x2, false)]),
+    ("co3", &[(AttrType::F32x3, false)]),
+    ("normal", &[(AttrType::F32x3, false)]),
+    ("uv", &[(AttrType::F32x2, false)]),
+    ("color", &[(AttrType::U8x3, true), (AttrType::F32x3, false)])
+];
+
+fn f2(v: &[f32]) -> [f32; 2] { [v[0], v[1]] }
+
+fn f3(v: &[f32]) -> [f32; 3] { [v[0], v[1], v[2]] }
+
+impl GeometryDesc {
+    /// Load a description, the format is picked from the extension (ron or json).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<GeometryDesc, GeometryError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let ext = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+        let desc: GeometryDesc = match ext.as_str() {
+            "ron" => ron::de::from_str(&content)?,
+            "json" => serde_json::from_str(&content)?,
+            _ => return Err(GeometryError::DataError(format!("unknown geometry format: {:?}", path)))
+        };
+        desc.validate()?;
+        Ok(desc)
+    }
+
+    pub fn is_interleaved(&self) -> bool {
+        match self.data { VertexData::Interleaved(_) => true, VertexData::Deinterleaved(_) => false }
+    }
+
+    fn attr_index(&self, name: &str) -> Option<usize> {
+        self.attributes.iter().position(|a| a.name == name)
+    }
+
+    fn has(&self, name: &str) -> bool { self.attr_index(name).is_some() }
+
+    pub fn len(&self) -> usize {
+        match &self.data {
+            VertexData::Interleaved(rows) => rows.len(),
+            VertexData::Deinterleaved(cols) => match (cols.first(), self.attributes.first()) {
+                (Some(col), Some(attr)) => col.len() / attr.ty.size(),
+                _ => 0
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Check the attributes against the shader inputs and the data against
+    /// the attributes, and pick the program family.
+    pub fn validate(&self) -> Result<GeoKind, GeometryError> {
+        for attr in &self.attributes {
+            match KNOWN_ATTRS.iter().find(|(name, _)| *name == attr.name) {
+                None => return Err(GeometryError::LayoutError(format!(
+                    "unknown shader input {:?}, expected one of {:?}",
+                    attr.name, KNOWN_ATTRS.iter().map(|(name, _)| *name).collect::<Vec<_>>()
+                ))),
+                Some((_, types)) if !types.contains(&(attr.ty, attr.normalized)) => {
+                    let types: Vec<_> = types.iter()
+                        .map(|(ty, normalized)| format!("{:?}{}", ty, if *normalized { " normalized" } else { "" }))
+                        .collect();
+                    return Err(GeometryError::LayoutError(format!("{:?} must be {}", attr.name, types.join(" or "))));
+                }
+                _ => ()
+            }
+            if self.attributes.iter().filter(|a| a.name == attr.name).count() > 1 {
+                return Err(GeometryError::LayoutError(format!("{:?} is declared twice", attr.name)));
+            }
+        }
+        // the vertices are built by attribute name, their order is free
+        let mut names: Vec<&str> = self.attributes.iter().map(|a| a.name.as_str()).collect();
+        names.sort();
+        let kind = match names.as_slice() {
+            ["co", "color"] => GeoKind::Flat,
+            ["co", "uv"] => GeoKind::Textured,
+            _ if self.has("co3") && !self.has("co") => GeoKind::Mesh,
+            _ => return Err(GeometryError::LayoutError(format!(
+                "no vertex type for {:?}, use [co, color], [co, uv] or co3 with normal, uv or color",
+                names
+            )))
+        };
+        if kind == GeoKind::Mesh && self.mode != GeoMode::Triangle {
+            return Err(GeometryError::LayoutError("3D geometries are triangles only".to_string()));
+        }
+        if kind == GeoKind::Textured && !self.is_interleaved() {
+            return Err(GeometryError::LayoutError("the textured layout can't be deinterleaved".to_string()));
+        }
+        let nb = self.len();
+        match &self.data {
+            VertexData::Interleaved(rows) => {
+                let stride: usize = self.attributes.iter().map(|a| a.ty.size()).sum();
+                if let Some(i) = rows.iter().position(|row| row.len() != stride) {
+                    return Err(GeometryError::DataError(format!("vertex {} hasn't {} values", i, stride)));
+                }
+            }
+            VertexData::Deinterleaved(cols) => {
+                if cols.len() != self.attributes.len() {
+                    return Err(GeometryError::DataError("one column is needed per attribute".to_string()));
+                }
+                for (col, attr) in cols.iter().zip(&self.attributes) {
+                    if col.len() != nb * attr.ty.size() {
+                        return Err(GeometryError::DataError(format!("{:?} hasn't {} vertices", attr.name, nb)));
+                    }
+                }
+            }
+        }
+        if let Some(i) = self.attributes.iter().position(|a| a.ty == AttrType::U8x3) {
+            if self.column(i).iter().any(|v| *v < 0.0 || *v > 255.0) {
+                return Err(GeometryError::DataError(format!("{:?} values must be in 0..=255", self.attributes[i].name)));
+            }
+        }
+        if nb == 0 || self.indices.as_ref().map_or(false, |inds| inds.is_empty()) {
+            return Err(GeometryError::DataError("the geometry is empty".to_string()));
+        }
+        if let Some(inds) = &self.indices {
+            if let Some(i) = inds.iter().find(|i| **i as usize >= nb) {
+                return Err(GeometryError::DataError(format!("index {} out of {} vertices", i, nb)));
+            }
+        }
+        Ok(kind)
+    }
+
+    /// Values of the attribute `idx`, flattened.
+    fn column(&self, idx: usize) -> Vec<f32> {
+        match &self.data {
+            VertexData::Deinterleaved(cols) => cols[idx].clone(),
+            VertexData::Interleaved(rows) => {
+                let start: usize = self.attributes[..idx].iter().map(|a| a.ty.size()).sum();
+                let size = self.attributes[idx].ty.size();
+                rows.iter().flat_map(|row| row[start..start + size].iter().cloned()).collect()
+            }
+        }
+    }
+
+    /// Values of the attribute `name` as the shader gets them, by vertex.
+    fn values<T, F>(&self, name: &str, f: F) -> Vec<T>
+    where F: Fn(&[f32]) -> T {
+        match self.attr_index(name) {
+            None => Vec::new(),
+            Some(idx) => {
+                let attr = &self.attributes[idx];
+                let mut column = self.column(idx);
+                if attr.normalized && attr.ty == AttrType::U8x3 {
+                    for v in column.iter_mut() { *v /= 255.0; }
+                }
+                column.chunks(attr.ty.size()).map(f).collect()
+            }
+        }
+    }
+
+    /// Build the tess, the ctx must be current. The colours are linear for
+    /// an sRGB framebuffer.
+    pub fn to_geometry<C>(&self, ctx: &mut C, srgb_fb: bool) -> Result<Geometry, GeometryError>
+    where C: GraphicsContext {
+        let kind = self.validate()?;
+        let rgb = |v: &[f32]| Color::fb_rgb(f3(v), srgb_fb);
+        let tess = match kind {
+            GeoKind::Mesh => {
+                let mesh = MeshData {
+                    positions: self.values("co3", f3),
+                    normals: self.values("normal", f3),
+                    uvs: self.values("uv", f2),
+                    colors: self.values("color", rgb),
+                    indices: self.indices.clone()
+                }.complete();
+                let method = match (self.is_interleaved(), self.indices.is_some()) {
+                    (true, false) => TessMethod::Direct,
+                    (true, true) => TessMethod::Indexed,
+                    (false, false) => TessMethod::DirectDeinter,
+                    (false, true) => TessMethod::IndexedDeinter
+                };
+                mesh.to_tess(ctx, method)?
+            }
+            GeoKind::Flat | GeoKind::Textured => {
+                let builder = match (kind, self.is_interleaved()) {
+                    (GeoKind::Flat, true) => {
+                        let verts: Vec<_> = self.values("co", f2).into_iter()
+                            .zip(self.values("color", rgb))
+                            .map(|(pos, rgb)| Vertex::new(pos, rgb))
+                            .collect();
+                        TessBuilder::new(ctx).add_vertices(verts)
+                    }
+                    (GeoKind::Flat, false) => {
+                        let poss: Vec<_> = self.values("co", f2).into_iter().map(Positions::new).collect();
+                        let cols: Vec<_> = self.values("color", rgb).into_iter().map(Colors::new).collect();
+                        TessBuilder::new(ctx).add_vertices(poss).add_vertices(cols)
+                    }
+                    _ => {
+                        let verts: Vec<_> = self.values("co", f2).into_iter()
+                            .zip(self.values("uv", f2))
+                            .map(|(pos, uv)| TexVertex::new(pos, uv))
+                            .collect();
+                        TessBuilder::new(ctx).add_vertices(verts)
+                    }
+                };
+                let builder = match &self.indices {
+                    Some(inds) => builder.set_indices(inds.as_slice()),
+                    None => builder
+                };
+                builder.set_mode(self.mode.mode()).build()?
+            }
+        };
+        Ok(Geometry { kind, tess })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc(attrs: &[(&str, AttrType, bool)], rows: Vec<Vec<f32>>, indices: Option<Vec<u32>>) -> GeometryDesc {
+        GeometryDesc {
+            attributes: attrs.iter()
+                .map(|(name, ty, normalized)| AttrDesc { name: name.to_string(), ty: *ty, normalized: *normalized })
+                .collect(),
+            data: VertexData::Interleaved(rows),
+            indices,
+            mode: GeoMode::Triangle
+        }
+    }
+
+    fn triangle(attrs: &[(&str, AttrType, bool)], color: [f32; 3]) -> GeometryDesc {
+        let row = |x: f32, y: f32| vec![x, y, color[0], color[1], color[2]];
+        desc(attrs, vec![row(0.0, 0.5), row(-0.5, -0.5), row(0.5, -0.5)], None)
+    }
+
+    const FLAT: [(&str, AttrType, bool); 2] = [("co", AttrType::F32x2, false), ("color", AttrType::U8x3, true)];
+
+    fn layout_error(geo: &GeometryDesc) -> bool {
+        match geo.validate() { Err(GeometryError::LayoutError(_)) => true, _ => false }
+    }
+
+    fn data_error(geo: &GeometryDesc) -> bool {
+        match geo.validate() { Err(GeometryError::DataError(_)) => true, _ => false }
+    }
+
+    #[test]
+    fn declared_types() {
+        assert_eq!(triangle(&FLAT, [255.0, 0.0, 0.0]).validate().unwrap(), GeoKind::Flat);
+        let float_color = [("color", AttrType::F32x3, false), ("co", AttrType::F32x2, false)];
+        let float = desc(&float_color, vec![vec![1.0, 0.0, 0.0, 0.0, 0.0]; 3], None);
+        assert_eq!(float.validate().unwrap(), GeoKind::Flat);
+        // the bytes and the floats give the same colours to the shader
+        let bytes = triangle(&FLAT, [255.0, 0.0, 0.0]);
+        assert_eq!(bytes.values("color", f3), float.values("color", f3));
+        // unnormalized bytes aren't colours
+        assert!(layout_error(&triangle(&[("co", AttrType::F32x2, false), ("color", AttrType::U8x3, false)], [0.0; 3])));
+        assert!(data_error(&triangle(&FLAT, [256.0, 0.0, 0.0])));
+    }
+
+    #[test]
+    fn rejected_layouts() {
+        let unknown = triangle(&[("co", AttrType::F32x2, false), ("colour", AttrType::U8x3, true)], [0.0; 3]);
+        assert!(layout_error(&unknown));
+        let twice = desc(&[("co", AttrType::F32x2, false), ("co", AttrType::F32x2, false)], vec![vec![0.0; 4]; 3], None);
+        assert!(layout_error(&twice));
+    }
+
+    #[test]
+    fn rejected_data() {
+        let mut short = triangle(&FLAT, [0.0; 3]);
+        if let VertexData::Interleaved(rows) = &mut short.data { rows[1].pop(); }
+        assert!(data_error(&short));
+        let out_of_range = GeometryDesc { indices: Some(vec![0, 1, 3]), ..triangle(&FLAT, [0.0; 3]) };
+        assert!(data_error(&out_of_range));
+        assert!(data_error(&desc(&FLAT, Vec::new(), None)));
+        assert!(data_error(&GeometryDesc { indices: Some(Vec::new()), ..triangle(&FLAT, [0.0; 3]) }));
+    }
+}
@@ -0,0 +1,421 @@
+use glutin::event::{ElementState, MouseButton, WindowEvent};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::batch::{BatchKey, BatchRenderer};
+use crate::clipboard::Clipboard;
+use crate::color::Color;
+use crate::material::{BlendMode, Scissor};
+use crate::shapes::ShapeBuilder;
+use crate::text::TextRenderer;
+
+/// First batch layer of the GUI, over the scene text. Every panel takes
+/// three layers: its back, its widgets and its labels.
+pub const UI_LAYER: u16 = 32;
+
+type WidgetId = u64;
+
+/// Colours and metrics of the widgets.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UiStyle {
+    pub panel: Color,
+    pub title_bar: Color,
+    pub widget: Color,
+    pub hot: Color,
+    pub active: Color,
+    pub accent: Color,
+    pub text: Color,
+    pub border: Color,
+    pub text_px: f32,
+    pub row_height: f32,
+    pub padding: f32,
+    pub rounding: f32,
+    /// Panel border, none at 0.
+    pub border_width: f32
+}
+
+impl Default for UiStyle {
+    fn default() -> Self {
+        UiStyle {
+            panel: Color::from_hex("#202028").unwrap(),
+            title_bar: Color::from_hex("#34344a").unwrap(),
+            widget: Color::from_hex("#3a3a48").unwrap(),
+            hot: Color::from_hex("#4a4a60").unwrap(),
+            active: Color::from_hex("#5a5a78").unwrap(),
+            accent: Color::named("coral").unwrap(),
+            text: Color::from_hex("#e8e8f0").unwrap(),
+            border: Color::from_hex("#50506a").unwrap(),
+            text_px: 16.0,
+            row_height: 26.0,
+            padding: 8.0,
+            rounding: 4.0,
+            border_width: 1.0
+        }
+    }
+}
+
+#[derive(Default)]
+struct UiInput {
+    cursor: Option<[f32; 2]>,
+    down: bool,
+    pressed: bool,
+    released: bool,
+    chars: Vec<char>
+}
+
+struct Label {
+    pos: [f32; 2],
+    color: Color,
+    text: String
+}
+
+/// What a panel submits, its back first.
+struct PanelDraw {
+    rect: [f32; 4],
+    back: ShapeBuilder,
+    front: ShapeBuilder,
+    labels: Vec<Label>
+}
+
+struct Row {
+    x: f32,
+    y: f32,
+    width: f32
+}
+
+/// Immediate mode GUI context of a window. Every frame, the widgets are
+/// declared between `begin` and `end` and report what the user did with
+/// them since the last frame; the state kept between frames is the panel
+/// positions and the widget being used.
+///
+/// Fed by `handle_event`, drawn through the window `BatchRenderer`.
+pub struct Ui {
+    pub style: UiStyle,
+    input: UiInput,
+    size: [u32; 2],
+    srgb: bool,
+    hot: Option<WidgetId>,
+    active: Option<WidgetId>,
+    focus: Option<WidgetId>,
+    panel_pos: HashMap<WidgetId, [f32; 2]>,
+    /// Panel rects of the last frame, for the event consumption.
+    last_rects: Vec<[f32; 4]>,
+    panels: Vec<PanelDraw>,
+    current: Option<(WidgetId, Row)>,
+    drag_from: [f32; 2],
+    /// For the copy, cut and paste of the text fields.
+    clipboard: Option<Clipboard>
+}
+
+fn id_of(scope: Option<WidgetId>, label: &str) -> WidgetId {
+    let mut hasher = DefaultHasher::new();
+    scope.hash(&mut hasher);
+    label.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn contains(rect: [f32; 4], p: [f32; 2]) -> bool {
+    p[0] >= rect[0] && p[1] >= rect[1] && p[0] < rect[0] + rect[2] && p[1] < rect[1] + rect[3]
+}
+
+impl Ui {
+    /// `srgb` if the window framebuffer is sRGB, see `ShapeBuilder`.
+    pub fn new(srgb: bool) -> Self {
+        Ui {
+            style: UiStyle::default(),
+            input: UiInput::default(),
+            size: [1, 1],
+            srgb,
+            hot: None,
+            active: None,
+            focus: None,
+            panel_pos: HashMap::new(),
+            last_rects: Vec::new(),
+            panels: Vec::new(),
+            current: None,
+            drag_from: [0.0, 0.0],
+            clipboard: None
+        }
+    }
+
+    pub fn set_clipboard(&mut self, clipboard: Clipboard) { self.clipboard = Some(clipboard); }
+
+    fn over_panel(&self) -> bool {
+        self.input.cursor.map_or(false, |p| self.last_rects.iter().any(|rect| contains(*rect, p)))
+    }
+
+    /// True if a text field has the keyboard.
+    pub fn wants_keyboard(&self) -> bool { self.focus.is_some() }
+
+    /// Feed a window event, returns true if the GUI used it: the mouse over a
+    /// panel or dragging a widget, the keyboard for a focused text field.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let was_over = self.over_panel();
+                self.input.cursor = Some([position.x as f32, position.y as f32]);
+                was_over || self.over_panel() || self.active.is_some()
+            }
+            WindowEvent::CursorLeft { .. } => {
+                let was_over = self.over_panel();
+                self.input.cursor = None;
+                was_over
+            }
+            WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                let pressed = *state == ElementState::Pressed;
+                self.input.down = pressed && self.over_panel();
+                if pressed {
+                    // a click anywhere else drops the text field focus
+                    if !self.over_panel() { self.focus = None; return false; }
+                    self.input.pressed = true;
+                }
+                else {
+                    if self.active.is_none() { return false; }
+                    self.input.released = true;
+                }
+                true
+            }
+            WindowEvent::ReceivedCharacter(c) if self.focus.is_some() => {
+                self.input.chars.push(*c);
+                true
+            }
+            WindowEvent::KeyboardInput { .. } if self.focus.is_some() => true,
+            _ => false
+        }
+    }
+
+    fn shapes(&self) -> ShapeBuilder { ShapeBuilder::new().viewport(self.size).srgb(self.srgb) }
+
+    /// Start the frame, for a window of `size` pixels.
+    pub fn begin(&mut self, size: [u32; 2]) {
+        self.size = size;
+        self.hot = None;
+        self.panels.clear();
+    }
+
+    /// Start a panel, draggable by its title, at `pos` the first time.
+    pub fn begin_panel(&mut self, title: &str, pos: [f32; 2], width: f32) {
+        let id = id_of(None, title);
+        let style = self.style;
+        let bar = style.row_height;
+        let mut pos = *self.panel_pos.entry(id).or_insert(pos);
+        let cursor = self.input.cursor.unwrap_or([-1.0, -1.0]);
+        if self.active == Some(id) {
+            pos = [cursor[0] - self.drag_from[0], cursor[1] - self.drag_from[1]];
+            self.panel_pos.insert(id, pos);
+        }
+        else if self.input.pressed && self.active.is_none() && contains([pos[0], pos[1], width, bar], cursor) {
+            self.active = Some(id);
+            self.drag_from = [cursor[0] - pos[0], cursor[1] - pos[1]];
+        }
+        let front = self.shapes().color(style.title_bar).rounded_rect(pos, [width, bar], style.rounding);
+        self.panels.push(PanelDraw {
+            rect: [pos[0], pos[1], width, bar],
+            back: self.shapes(),
+            front,
+            labels: vec![Label {
+                pos: [pos[0] + style.padding, pos[1] + (bar - style.text_px) / 2.0],
+                color: style.text,
+                text: title.to_string()
+            }]
+        });
+        self.current = Some((id, Row {
+            x: pos[0] + style.padding,
+            y: pos[1] + bar + style.padding,
+            width: width - 2.0 * style.padding
+        }));
+    }
+
+    pub fn end_panel(&mut self) {
+        if let Some((_, row)) = self.current.take() {
+            let style = self.style;
+            if let Some(panel) = self.panels.last_mut() {
+                panel.rect[3] = row.y - panel.rect[1];
+                let [x, y, w, h] = panel.rect;
+                let b = style.border_width;
+                let mut back = std::mem::replace(&mut panel.back, ShapeBuilder::new());
+                if b > 0.0 { back = back.color(style.border).rounded_rect([x, y], [w, h], style.rounding); }
+                panel.back = back
+                    .color(style.panel)
+                    .rounded_rect([x + b, y + b], [w - 2.0 * b, h - 2.0 * b], (style.rounding - b).max(0.0));
+            }
+        }
+    }
+
+    /// Next row of the current panel, with the id of `label` in it.
+    fn row(&mut self, label: &str) -> Option<(WidgetId, [f32; 4])> {
+        let (scope, row) = self.current.as_mut()?;
+        let rect = [row.x, row.y, row.width, self.style.row_height];
+        row.y += self.style.row_height + self.style.padding / 2.0;
+        Some((id_of(Some(*scope), label), rect))
+    }
+
+    /// Hot and active handling of a clickable rect, true when clicked.
+    fn interact(&mut self, id: WidgetId, rect: [f32; 4]) -> bool {
+        let inside = self.input.cursor.map_or(false, |p| contains(rect, p));
+        if inside { self.hot = Some(id); }
+        if inside && self.input.pressed && self.active.is_none() { self.active = Some(id); }
+        self.input.released && self.active == Some(id) && inside
+    }
+
+    fn fill(&self, id: WidgetId) -> Color {
+        if self.active == Some(id) { self.style.active }
+        else if self.hot == Some(id) { self.style.hot }
+        else { self.style.widget }
+    }
+
+    fn draw<F>(&mut self, f: F)
+    where F: FnOnce(ShapeBuilder) -> ShapeBuilder {
+        if let Some(panel) = self.panels.last_mut() {
+            let front = std::mem::replace(&mut panel.front, ShapeBuilder::new());
+            panel.front = f(front);
+        }
+    }
+
+    fn text(&mut self, pos: [f32; 2], color: Color, text: String) {
+        if let Some(panel) = self.panels.last_mut() { panel.labels.push(Label { pos, color, text }); }
+    }
+
+    fn text_y(&self, rect: [f32; 4]) -> f32 { rect[1] + (rect[3] - self.style.text_px) / 2.0 }
+
+    pub fn label(&mut self, text: &str) {
+        if let Some((_, rect)) = self.row(text) {
+            let (y, color) = (self.text_y(rect), self.style.text);
+            self.text([rect[0], y], color, text.to_string());
+        }
+    }
+
+    pub fn button(&mut self, label: &str) -> bool {
+        let (id, rect) = match self.row(label) { Some(row) => row, None => return false };
+        let clicked = self.interact(id, rect);
+        let (fill, rounding) = (self.fill(id), self.style.rounding);
+        self.draw(|sb| sb.color(fill).rounded_rect([rect[0], rect[1]], [rect[2], rect[3]], rounding));
+        let (y, color) = (self.text_y(rect), self.style.text);
+        self.text([rect[0] + self.style.padding, y], color, label.to_string());
+        clicked
+    }
+
+    /// Returns true when `value` changed.
+    pub fn checkbox(&mut self, label: &str, value: &mut bool) -> bool {
+        let (id, rect) = match self.row(label) { Some(row) => row, None => return false };
+        let clicked = self.interact(id, rect);
+        if clicked { *value = !*value; }
+        let side = rect[3] - 8.0;
+        let boxed = [rect[0], rect[1] + 4.0];
+        let (fill, accent, checked) = (self.fill(id), self.style.accent, *value);
+        self.draw(|sb| {
+            let sb = sb.color(fill).rect(boxed, [side, side]);
+            if checked { sb.color(accent).rect([boxed[0] + 4.0, boxed[1] + 4.0], [side - 8.0, side - 8.0]) }
+            else { sb }
+        });
+        let (y, color) = (self.text_y(rect), self.style.text);
+        self.text([rect[0] + side + self.style.padding, y], color, label.to_string());
+        clicked
+    }
+
+    /// Horizontal slider between `min` and `max`, returns true when `value`
+    /// changed.
+    pub fn slider(&mut self, label: &str, value: &mut f32, min: f32, max: f32) -> bool {
+        let (id, rect) = match self.row(label) { Some(row) => row, None => return false };
+        self.interact(id, rect);
+        let mut changed = false;
+        if self.active == Some(id) {
+            if let Some(cursor) = self.input.cursor {
+                let t = ((cursor[0] - rect[0]) / rect[2]).max(0.0).min(1.0);
+                let new = min + (max - min) * t;
+                changed = new != *value;
+                *value = new;
+            }
+        }
+        let t = if max > min { ((*value - min) / (max - min)).max(0.0).min(1.0) } else { 0.0 };
+        let (fill, accent, rounding) = (self.fill(id), self.style.accent, self.style.rounding);
+        self.draw(|sb| sb
+            .color(fill).rounded_rect([rect[0], rect[1]], [rect[2], rect[3]], rounding)
+            .color(accent).rect([rect[0] + (rect[2] - 8.0) * t, rect[1]], [8.0, rect[3]]));
+        let (y, color) = (self.text_y(rect), self.style.text);
+        self.text([rect[0] + self.style.padding, y], color, format!("{}: {:.2}", label, value));
+        changed
+    }
+
+    /// Single line text input, focused by a click, with the clipboard on
+    /// Ctrl+C, Ctrl+X and Ctrl+V. Returns true when `value` changed.
+    pub fn text_field(&mut self, label: &str, value: &mut String) -> bool {
+        let (id, rect) = match self.row(label) { Some(row) => row, None => return false };
+        if self.interact(id, rect) { self.focus = Some(id); }
+        let mut changed = false;
+        if self.focus == Some(id) {
+            for c in self.input.chars.drain(..) {
+                match c {
+                    '\u{8}' => { changed |= value.pop().is_some(); }
+                    '\r' | '\n' | '\u{1b}' => { self.focus = None; break; }
+                    // Ctrl+C and Ctrl+X
+                    '\u{3}' | '\u{18}' => if let Some(clipboard) = &self.clipboard {
+                        match clipboard.set_text(value) {
+                            Ok(()) if c == '\u{18}' => { changed |= !value.is_empty(); value.clear(); }
+                            Ok(()) => (),
+                            Err(e) => eprintln!("{}", e)
+                        }
+                    },
+                    // Ctrl+V, the first line only
+                    '\u{16}' => if let Some(clipboard) = &self.clipboard {
+                        match clipboard.get_text() {
+                            Ok(text) => {
+                                let line = text.lines().next().unwrap_or_default();
+                                value.extend(line.chars().filter(|c| !c.is_control()));
+                                changed |= !line.is_empty();
+                            }
+                            Err(e) => eprintln!("{}", e)
+                        }
+                    },
+                    c if !c.is_control() => { value.push(c); changed = true; }
+                    _ => ()
+                }
+            }
+        }
+        let focused = self.focus == Some(id);
+        let fill = if focused { self.style.active } else { self.fill(id) };
+        let (accent, rounding) = (self.style.accent, self.style.rounding);
+        self.draw(|sb| {
+            let sb = sb.color(fill).rounded_rect([rect[0], rect[1]], [rect[2], rect[3]], rounding);
+            if focused { sb.color(accent).rect([rect[0], rect[1] + rect[3] - 2.0], [rect[2], 2.0]) } else { sb }
+        });
+        let shown = if value.is_empty() && !focused { format!("<{}>", label) }
+            else if focused { format!("{}|", value) }
+            else { value.clone() };
+        let (y, color) = (self.text_y(rect), self.style.text);
+        self.text([rect[0] + self.style.padding, y], color, shown);
+        changed
+    }
+
+    /// Submit the frame to `batch`, and reset the input of the frame.
+    pub fn end(&mut self, batch: &mut BatchRenderer, text: &mut TextRenderer) {
+        if self.current.is_some() { self.end_panel(); }
+        if self.input.released || !self.input.down { self.active = None; }
+        self.input.pressed = false;
+        self.input.released = false;
+        self.input.chars.clear();
+        self.last_rects = self.panels.iter().map(|panel| panel.rect).collect();
+        let size = self.size;
+        for (i, panel) in self.panels.drain(..).enumerate() {
+            // the last panel declared on top
+            let layer = UI_LAYER + 3 * i as u16;
+            // the panel part inside the window, a dragged panel can go out
+            let [x, y, w, h] = panel.rect;
+            let (x0, y0) = (x.max(0.0) as u32, y.max(0.0) as u32);
+            let x1 = (x + w).max(0.0).min(size[0] as f32) as u32;
+            let y1 = (y + h).max(0.0).min(size[1] as f32) as u32;
+            // checked in pixels, a sliver under one is an empty scissor
+            if x1 <= x0 || y1 <= y0 { continue; }
+            let clip = Some(Scissor::from_top_left([x0, y0], [x1 - x0, y1 - y0], size[1]));
+            let key = BatchKey { layer, blend: BlendMode::Alpha, clip, ..BatchKey::default() };
+            batch.shape(key, panel.back);
+            batch.shape(BatchKey { layer: layer + 1, ..key }, panel.front);
+            let text_key = BatchKey { layer: layer + 2, ..key };
+            for label in panel.labels {
+                let layout = text.layout(&label.text, self.style.text_px, None);
+                text.draw_layout_with(batch, size, text_key, label.pos, label.color, &layout);
+            }
+        }
+    }
+}
@@ -1,16 +1,53 @@
 use luminance_derive::{Semantics, Vertex};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
-pub fn new_nb() -> f32 {
-    (rand::thread_rng().gen_range(0, 100) as f32) / 100.0
+use crate::color::Color;
+
+/// Seeded random source, so the colours and the generated geometry replay
+/// identically from the same seed.
+pub struct DemoRng {
+    rng: StdRng
+}
+
+impl DemoRng {
+    pub fn new(seed: u64) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes[..8].copy_from_slice(&seed.to_le_bytes());
+        DemoRng { rng: StdRng::from_seed(bytes) }
+    }
+
+    /// Seed picked at random, to be printed so the run can be replayed.
+    pub fn random_seed() -> u64 { rand::thread_rng().gen() }
+
+    /// Child generator, its sequence only depends on this one's state: the
+    /// windows get one each, in creation order.
+    pub fn fork(&mut self) -> DemoRng { DemoRng::new(self.rng.gen()) }
+
+    /// Number in [0, 1), in steps of 0.01.
+    pub fn nb(&mut self) -> f32 {
+        (self.rng.gen_range(0, 100) as f32) / 100.0
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Semantics)]
 pub enum Semantics {
     #[sem(name = "co", repr = "[f32; 2]", wrapper = "VertexPosition")]
     Position,
-    #[sem(name = "color", repr = "[u8; 3]", wrapper = "VertexColor")]
-    Color
+    #[sem(name = "co3", repr = "[f32; 3]", wrapper = "VertexPosition3")]
+    Position3,
+    #[sem(name = "normal", repr = "[f32; 3]", wrapper = "VertexNormal")]
+    Normal,
+    #[sem(name = "uv", repr = "[f32; 2]", wrapper = "VertexUV")]
+    TexCoord,
+    #[sem(name = "color", repr = "[f32; 3]", wrapper = "VertexColor")]
+    Color,
+    #[sem(name = "inst_offset", repr = "[f32; 2]", wrapper = "VertexInstOffset")]
+    InstOffset,
+    #[sem(name = "inst_color", repr = "[f32; 3]", wrapper = "VertexInstColor")]
+    InstColor,
+    #[sem(name = "inst_scale", repr = "f32", wrapper = "VertexInstScale")]
+    InstScale
 }
 
 #[repr(C)]
@@ -18,23 +55,134 @@ pub enum Semantics {
 #[vertex(sem = "Semantics")]
 pub struct Vertex {
     pos: VertexPosition,
-    #[vertex(normalized = "true")]
     rgb: VertexColor
 }
 
+impl Vertex {
+    pub fn new(pos: [f32; 2], rgb: [f32; 3]) -> Self {
+        Vertex {pos: VertexPosition::new(pos), rgb: VertexColor::new(rgb)}
+    }
+
+    pub fn pos(&self) -> [f32; 2] { *self.pos }
+
+    pub fn rgb(&self) -> [f32; 3] { *self.rgb }
+
+    /// The colour for the framebuffer, see `Color::vertex_rgb`.
+    pub fn for_fb(&self, srgb_fb: bool) -> Self {
+        Vertex { pos: self.pos, rgb: VertexColor::new(Color::fb_rgb(*self.rgb, srgb_fb)) }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, Vertex)]
 #[vertex(sem = "Semantics")]
 pub struct Positions { pos: VertexPosition }
 
+impl Positions {
+    pub fn new(pos: [f32; 2]) -> Self { Positions {pos: VertexPosition::new(pos)} }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, Vertex)]
 #[vertex(sem = "Semantics")]
 pub struct Colors {
-    #[vertex(normalized = "true")]
     color: VertexColor
 }
 
+impl Colors {
+    pub fn new(rgb: [f32; 3]) -> Self { Colors {color: VertexColor::new(rgb)} }
+
+    pub fn for_fb(&self, srgb_fb: bool) -> Self { Colors::new(Color::fb_rgb(*self.color, srgb_fb)) }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[vertex(sem = "Semantics")]
+pub struct TexVertex {
+    pos: VertexPosition,
+    uv: VertexUV
+}
+
+impl TexVertex {
+    pub fn new(pos: [f32; 2], uv: [f32; 2]) -> Self {
+        TexVertex {pos: VertexPosition::new(pos), uv: VertexUV::new(uv)}
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[vertex(sem = "Semantics", instanced = "true")]
+pub struct Instance {
+    offset: VertexInstOffset,
+    color: VertexInstColor,
+    scale: VertexInstScale
+}
+
+impl Instance {
+    pub fn for_fb(&self, srgb_fb: bool) -> Self {
+        Instance { color: VertexInstColor::new(Color::fb_rgb(*self.color, srgb_fb)), ..*self }
+    }
+}
+
+pub const INSTANCE_GRID: u32 = 64;
+
+/// A grid of `INSTANCE_GRID`² instances covering the whole viewport.
+pub fn instance_grid(rng: &mut DemoRng) -> Vec<Instance> {
+    let step = 2.0 / INSTANCE_GRID as f32;
+    let mut rnd = |max: u8| (rng.nb() * max as f32) as u8;
+    (0..INSTANCE_GRID * INSTANCE_GRID).map(|i| {
+        let (x, y) = ((i % INSTANCE_GRID) as f32, (i / INSTANCE_GRID) as f32);
+        Instance {
+            offset: VertexInstOffset::new([-1.0 + (x + 0.5) * step, -1.0 + (y + 0.5) * step]),
+            color: VertexInstColor::new(
+                Color::from_rgb8([155 + rnd(100), 155 + rnd(100), 155 + rnd(100)]).vertex_rgb(false)
+            ),
+            scale: VertexInstScale::new(step * 0.9)
+        }
+    }).collect()
+}
+
+/// Wobble the vertices of `base` into `out` at time `t` (in seconds).
+pub fn wave_verts(base: &[Vertex], t: f32, out: &mut [Vertex]) {
+    for (i, (src, dst)) in base.iter().zip(out.iter_mut()).enumerate() {
+        let [x, y] = *src.pos;
+        let phase = t * 2.0 + i as f32;
+        *dst = Vertex {
+            pos: VertexPosition::new([x + phase.cos() * 0.05, y + phase.sin() * 0.05]),
+            rgb: src.rgb
+        };
+    }
+}
+
+/// Move the instances of `base` into `out` as a wave running along the grid.
+pub fn wave_instances(base: &[Instance], t: f32, out: &mut [Instance]) {
+    for (src, dst) in base.iter().zip(out.iter_mut()) {
+        let [x, y] = *src.offset;
+        let scale = *src.scale;
+        let dy = (x * 6.0 + t * 3.0).sin() * scale * 0.5;
+        *dst = Instance {
+            offset: VertexInstOffset::new([x, y + dy]),
+            color: src.color,
+            scale: VertexInstScale::new(scale * (1.0 + (y * 4.0 + t * 2.0).sin() * 0.2))
+        };
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[vertex(sem = "Semantics")]
+pub struct BatchVertex {
+    pos: VertexPosition,
+    uv: VertexUV,
+    rgb: VertexColor
+}
+
+impl BatchVertex {
+    pub fn new(pos: [f32; 2], uv: [f32; 2], rgb: [f32; 3]) -> Self {
+        BatchVertex {pos: VertexPosition::new(pos), uv: VertexUV::new(uv), rgb: VertexColor::new(rgb)}
+    }
+}
+
 pub struct TriFull<'a> {
     pub tri_verts: [Vertex; 6],
     pub tri_inds: [u32; 6],
@@ -44,12 +192,12 @@ pub struct TriFull<'a> {
 
 pub const TRIS_FIRST: TriFull = TriFull {
     tri_verts: [
-        Vertex {pos: VertexPosition::new([-0.5, -0.5]), rgb: VertexColor::new([0, 255, 0])},
-        Vertex {pos: VertexPosition::new([0.5, 0.0]), rgb: VertexColor::new([0, 0, 255])},
-        Vertex {pos: VertexPosition::new([-0.5, 0.5]), rgb: VertexColor::new([255, 0, 0])},
-        Vertex {pos: VertexPosition::new([0.5, 0.5]), rgb: VertexColor::new([255, 51, 255])},
-        Vertex {pos: VertexPosition::new([-0.5, 0.0]), rgb: VertexColor::new([51, 255, 255])},
-        Vertex {pos: VertexPosition::new([0.5, -0.5]), rgb: VertexColor::new([51, 51, 255])}
+        Vertex {pos: VertexPosition::new([-0.5, -0.5]), rgb: VertexColor::new([0.0, 1.0, 0.0])},
+        Vertex {pos: VertexPosition::new([0.5, 0.0]), rgb: VertexColor::new([0.0, 0.0, 1.0])},
+        Vertex {pos: VertexPosition::new([-0.5, 0.5]), rgb: VertexColor::new([1.0, 0.0, 0.0])},
+        Vertex {pos: VertexPosition::new([0.5, 0.5]), rgb: VertexColor::new([1.0, 0.2, 1.0])},
+        Vertex {pos: VertexPosition::new([-0.5, 0.0]), rgb: VertexColor::new([0.2, 1.0, 1.0])},
+        Vertex {pos: VertexPosition::new([0.5, -0.5]), rgb: VertexColor::new([0.2, 0.2, 1.0])}
     ],
     tri_inds: [
         0, 1, 2, // First triangle.
@@ -64,23 +212,23 @@ pub const TRIS_FIRST: TriFull = TriFull {
         Positions {pos: VertexPosition::new([0.5, 0.5])}
     ],
     tri_deint_col_verts: &[
-        Colors {color: VertexColor::new([0, 255, 0])},
-        Colors {color: VertexColor::new([0, 0, 255])},
-        Colors {color: VertexColor::new([255, 0, 0])},
-        Colors {color: VertexColor::new([255, 51, 255])},
-        Colors {color: VertexColor::new([51, 255, 255])},
-        Colors {color: VertexColor::new([51, 51, 255])}
+        Colors {color: VertexColor::new([0.0, 1.0, 0.0])},
+        Colors {color: VertexColor::new([0.0, 0.0, 1.0])},
+        Colors {color: VertexColor::new([1.0, 0.0, 0.0])},
+        Colors {color: VertexColor::new([1.0, 0.2, 1.0])},
+        Colors {color: VertexColor::new([0.2, 1.0, 1.0])},
+        Colors {color: VertexColor::new([0.2, 0.2, 1.0])}
     ]
 };
 
 pub const TRIS_FULL: TriFull = TriFull {
     tri_verts: [
-        Vertex {pos: VertexPosition::new([0.5, -0.5]), rgb: VertexColor::new([0, 255, 0])},
-        Vertex {pos: VertexPosition::new([0.0, 0.5]), rgb: VertexColor::new([0, 0, 255])},
-        Vertex {pos: VertexPosition::new([-0.5, -0.5]), rgb: VertexColor::new([255, 0, 0])},
-        Vertex {pos: VertexPosition::new([-0.5, 0.5]), rgb: VertexColor::new([255, 51, 255])},
-        Vertex {pos: VertexPosition::new([0.0, -0.5]), rgb: VertexColor::new([51, 255, 255])},
-        Vertex {pos: VertexPosition::new([0.5, 0.5]), rgb: VertexColor::new([51, 51, 255])}
+        Vertex {pos: VertexPosition::new([0.5, -0.5]), rgb: VertexColor::new([0.0, 1.0, 0.0])},
+        Vertex {pos: VertexPosition::new([0.0, 0.5]), rgb: VertexColor::new([0.0, 0.0, 1.0])},
+        Vertex {pos: VertexPosition::new([-0.5, -0.5]), rgb: VertexColor::new([1.0, 0.0, 0.0])},
+        Vertex {pos: VertexPosition::new([-0.5, 0.5]), rgb: VertexColor::new([1.0, 0.2, 1.0])},
+        Vertex {pos: VertexPosition::new([0.0, -0.5]), rgb: VertexColor::new([0.2, 1.0, 1.0])},
+        Vertex {pos: VertexPosition::new([0.5, 0.5]), rgb: VertexColor::new([0.2, 0.2, 1.0])}
     ],
     tri_inds: [
         0, 1, 2, // First triangle.
@@ -95,15 +243,30 @@ pub const TRIS_FULL: TriFull = TriFull {
         Positions {pos: VertexPosition::new([0.5, 0.5])}
     ],
     tri_deint_col_verts: &[
-        Colors {color: VertexColor::new([0, 255, 0])},
-        Colors {color: VertexColor::new([0, 0, 255])},
-        Colors {color: VertexColor::new([255, 0, 0])},
-        Colors {color: VertexColor::new([255, 51, 255])},
-        Colors {color: VertexColor::new([51, 255, 255])},
-        Colors {color: VertexColor::new([51, 51, 255])}
+        Colors {color: VertexColor::new([0.0, 1.0, 0.0])},
+        Colors {color: VertexColor::new([0.0, 0.0, 1.0])},
+        Colors {color: VertexColor::new([1.0, 0.0, 0.0])},
+        Colors {color: VertexColor::new([1.0, 0.2, 1.0])},
+        Colors {color: VertexColor::new([0.2, 1.0, 1.0])},
+        Colors {color: VertexColor::new([0.2, 0.2, 1.0])}
     ]
 };
 
+// UVs go past [0, 1] to show the sampler wrapping
+pub const TEX_QUAD: [TexVertex; 4] = [
+    TexVertex {pos: VertexPosition::new([-0.8, -0.8]), uv: VertexUV::new([-0.5, -0.5])},
+    TexVertex {pos: VertexPosition::new([0.8, -0.8]), uv: VertexUV::new([1.5, -0.5])},
+    TexVertex {pos: VertexPosition::new([0.8, 0.8]), uv: VertexUV::new([1.5, 1.5])},
+    TexVertex {pos: VertexPosition::new([-0.8, 0.8]), uv: VertexUV::new([-0.5, 1.5])}
+];
+
+pub const THUMB_QUAD: [TexVertex; 4] = [
+    TexVertex {pos: VertexPosition::new([0.45, 0.45]), uv: VertexUV::new([0.0, 0.0])},
+    TexVertex {pos: VertexPosition::new([0.95, 0.45]), uv: VertexUV::new([1.0, 0.0])},
+    TexVertex {pos: VertexPosition::new([0.95, 0.95]), uv: VertexUV::new([1.0, 1.0])},
+    TexVertex {pos: VertexPosition::new([0.45, 0.95]), uv: VertexUV::new([0.0, 1.0])}
+];
+
 #[derive(Copy, Clone, Debug)]
 pub enum TessMethod {
     Direct,
@@ -111,3 +274,48 @@ pub enum TessMethod {
     DirectDeinter,
     IndexedDeinter
 }
+
+/// What a window shows: the triangles with a tess method or instanced, the
+/// textured quad or the batched shapes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DemoMode {
+    Flat(TessMethod),
+    Instanced,
+    IndexedInstanced,
+    Textured,
+    Shapes
+}
+
+impl DemoMode {
+    pub fn next(self) -> Self {
+        match self {
+            DemoMode::Flat(TessMethod::Direct) => DemoMode::Flat(TessMethod::Indexed),
+            DemoMode::Flat(TessMethod::Indexed) => DemoMode::Flat(TessMethod::DirectDeinter),
+            DemoMode::Flat(TessMethod::DirectDeinter) => DemoMode::Flat(TessMethod::IndexedDeinter),
+            DemoMode::Flat(TessMethod::IndexedDeinter) => DemoMode::Instanced,
+            DemoMode::Instanced => DemoMode::IndexedInstanced,
+            DemoMode::IndexedInstanced => DemoMode::Textured,
+            DemoMode::Textured => DemoMode::Shapes,
+            DemoMode::Shapes => DemoMode::Flat(TessMethod::Direct)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(rng: &mut DemoRng) -> Vec<f32> { (0..16).map(|_| rng.nb()).collect() }
+
+    #[test]
+    fn same_seed_same_sequences() {
+        let (mut a, mut b) = (DemoRng::new(42), DemoRng::new(42));
+        assert_eq!(numbers(&mut a), numbers(&mut b));
+        let (mut fork_a, mut fork_b) = (a.fork(), b.fork());
+        assert_eq!(numbers(&mut fork_a), numbers(&mut fork_b));
+        // the forks moved their parents alike
+        assert_eq!(numbers(&mut a), numbers(&mut b));
+        assert_ne!(numbers(&mut DemoRng::new(42)), numbers(&mut DemoRng::new(43)));
+        assert!(numbers(&mut a).iter().all(|n| *n >= 0.0 && *n < 1.0));
+    }
+}
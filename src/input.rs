@@ -0,0 +1,268 @@
+use glutin::event::{ElementState, Force, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent};
+use glutin::window::WindowId;
+
+/// Pixels of a scroll line, for the touchpads giving pixel deltas.
+const LINE_PX: f64 = 20.0;
+
+/// What touches the window.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PointerKind {
+    Finger,
+    /// A touch with an altitude angle, only a stylus gives it.
+    Pen
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TouchPoint {
+    pub id: u64,
+    pub kind: PointerKind,
+    /// In physical pixels.
+    pub pos: [f64; 2],
+    /// Pressure from 0 to 1, if the device has one.
+    pub force: Option<f64>,
+    /// Angle of a pen with the surface, in radians.
+    pub altitude: Option<f64>,
+    pub phase: TouchPhase
+}
+
+impl TouchPoint {
+    pub fn is_active(&self) -> bool {
+        self.phase == TouchPhase::Started || self.phase == TouchPhase::Moved
+    }
+}
+
+/// Lines of a wheel move, positive up and right.
+pub fn scroll_lines(delta: &MouseScrollDelta) -> [f64; 2] {
+    match delta {
+        MouseScrollDelta::LineDelta(x, y) => [*x as f64, *y as f64],
+        MouseScrollDelta::PixelDelta(pos) => [pos.x / LINE_PX, pos.y / LINE_PX]
+    }
+}
+
+pub(crate) fn rect_of(rects: &[(WindowId, [f64; 4])], id: WindowId) -> Option<[f64; 4]> {
+    rects.iter().find(|(win, _)| *win == id).map(|(_, rect)| *rect)
+}
+
+/// The window at a screen position, with the position in it.
+pub(crate) fn window_at(rects: &[(WindowId, [f64; 4])], p: [f64; 2]) -> Option<(WindowId, [f64; 2])> {
+    rects.iter()
+        .find(|(_, r)| p[0] >= r[0] && p[1] >= r[1] && p[0] < r[0] + r[2] && p[1] < r[1] + r[3])
+        .map(|(win, r)| (*win, [p[0] - r[0], p[1] - r[1]]))
+}
+
+/// The window under a cursor given by `window`, with the position in it.
+/// A window keeps the cursor while a button is held, even out of it, the
+/// window rects (`WinManager::screen_rects`) then give the one under it.
+pub fn cursor_target(
+    window: WindowId,
+    pos: [f64; 2],
+    rects: &[(WindowId, [f64; 4])]
+) -> Option<(WindowId, [f64; 2])> {
+    let rect = match rect_of(rects, window) {
+        Some(rect) => rect,
+        // no position on this platform, the cursor stays with the window
+        None => return Some((window, pos))
+    };
+    if pos[0] >= 0.0 && pos[1] >= 0.0 && pos[0] < rect[2] && pos[1] < rect[3] { return Some((window, pos)); }
+    window_at(rects, [rect[0] + pos[0], rect[1] + pos[1]])
+}
+
+/// Mouse, scroll and touch state of a window, from its events. The changes
+/// since the last frame (scroll, enter and leave, ended touches) last until
+/// `end_frame`, to call once the window state follows them.
+pub struct InputState {
+    scale: f64,
+    /// In physical pixels, none when the pointer is out of the window.
+    cursor: Option<[f64; 2]>,
+    buttons: Vec<MouseButton>,
+    /// In lines.
+    scroll: [f64; 2],
+    entered: bool,
+    left: bool,
+    touches: Vec<TouchPoint>,
+    changed: bool
+}
+
+impl InputState {
+    /// For a window with the `scale` factor.
+    pub fn new(scale: f64) -> Self {
+        InputState {
+            scale,
+            cursor: None,
+            buttons: Vec::new(),
+            scroll: [0.0, 0.0],
+            entered: false,
+            left: false,
+            touches: Vec::new(),
+            changed: false
+        }
+    }
+
+    /// Follow an event of the window, but the `CursorMoved` ones going
+    /// through `cursor_target` then `set_cursor`.
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::CursorEntered { .. } => self.entered = true,
+            WindowEvent::CursorLeft { .. } => self.set_cursor(None),
+            WindowEvent::MouseInput { state: ElementState::Pressed, button, .. } => {
+                if !self.buttons.contains(button) { self.buttons.push(*button); }
+            }
+            WindowEvent::MouseInput { state: ElementState::Released, button, .. } =>
+                self.buttons.retain(|b| b != button),
+            WindowEvent::MouseWheel { delta, .. } => {
+                let [dx, dy] = scroll_lines(delta);
+                self.scroll = [self.scroll[0] + dx, self.scroll[1] + dy];
+            }
+            WindowEvent::Touch(touch) => {
+                let altitude = match touch.force {
+                    Some(Force::Calibrated { altitude_angle, .. }) => altitude_angle,
+                    _ => None
+                };
+                let point = TouchPoint {
+                    id: touch.id,
+                    kind: if altitude.is_some() { PointerKind::Pen } else { PointerKind::Finger },
+                    pos: [touch.location.x, touch.location.y],
+                    force: touch.force.map(|force| force.normalized()),
+                    altitude,
+                    phase: touch.phase
+                };
+                match self.touches.iter_mut().find(|t| t.id == touch.id) {
+                    Some(found) => *found = point,
+                    None => self.touches.push(point)
+                }
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => self.scale = *scale_factor,
+            WindowEvent::Focused(false) => self.buttons.clear(),
+            _ => return
+        }
+        self.changed = true;
+    }
+
+    /// Move the cursor to `pos` in physical pixels, or out of the window.
+    pub fn set_cursor(&mut self, pos: Option<[f64; 2]>) {
+        if pos == self.cursor { return; }
+        match (self.cursor, pos) {
+            (None, Some(_)) => self.entered = true,
+            (Some(_), None) => self.left = true,
+            _ => ()
+        }
+        self.cursor = pos;
+        self.changed = true;
+    }
+
+    pub fn scale(&self) -> f64 { self.scale }
+
+    /// In physical pixels.
+    pub fn cursor(&self) -> Option<[f64; 2]> { self.cursor }
+
+    pub fn cursor_logical(&self) -> Option<[f64; 2]> {
+        self.cursor.map(|[x, y]| [x / self.scale, y / self.scale])
+    }
+
+    /// Held buttons, in press order.
+    pub fn buttons(&self) -> &[MouseButton] { &self.buttons }
+
+    /// Lines scrolled since the last frame, positive up and right.
+    pub fn scroll(&self) -> [f64; 2] { self.scroll }
+
+    /// The pointer came in since the last frame.
+    pub fn entered(&self) -> bool { self.entered }
+
+    /// The pointer went out since the last frame.
+    pub fn left(&self) -> bool { self.left }
+
+    /// The touches in progress, and the ones ended since the last frame.
+    pub fn touches(&self) -> &[TouchPoint] { &self.touches }
+
+    pub fn pen(&self) -> Option<&TouchPoint> { self.touches.iter().find(|t| t.kind == PointerKind::Pen) }
+
+    /// Anything new since the last frame.
+    pub fn changed(&self) -> bool { self.changed }
+
+    pub fn end_frame(&mut self) {
+        self.scroll = [0.0, 0.0];
+        self.entered = false;
+        self.left = false;
+        self.touches.retain(|t| t.is_active());
+        self.changed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glutin::dpi::PhysicalPosition;
+    use glutin::event::{DeviceId, Touch};
+
+    fn touch(input: &mut InputState, id: u64, phase: TouchPhase, altitude: Option<f64>) {
+        let force = altitude.map(|altitude| Force::Calibrated {
+            force: 0.5,
+            max_possible_force: 1.0,
+            altitude_angle: Some(altitude)
+        });
+        input.handle_event(&WindowEvent::Touch(Touch {
+            device_id: unsafe { DeviceId::dummy() },
+            phase,
+            location: PhysicalPosition::new(10.0, 20.0),
+            force,
+            id
+        }));
+    }
+
+    #[test]
+    fn enter_leave_latched() {
+        let mut input = InputState::new(2.0);
+        input.set_cursor(Some([10.0, 10.0]));
+        assert!(input.entered() && !input.left() && input.changed());
+        assert_eq!(input.cursor_logical(), Some([5.0, 5.0]));
+        input.end_frame();
+        assert!(!input.entered() && !input.changed());
+        // out and back in the same frame, both are seen
+        input.set_cursor(None);
+        input.set_cursor(Some([12.0, 10.0]));
+        assert!(input.entered() && input.left());
+        input.end_frame();
+        input.set_cursor(Some([12.0, 10.0]));
+        assert!(!input.changed());
+    }
+
+    #[test]
+    fn ended_touches_last_a_frame() {
+        let mut input = InputState::new(1.0);
+        touch(&mut input, 1, TouchPhase::Started, None);
+        touch(&mut input, 2, TouchPhase::Started, None);
+        touch(&mut input, 1, TouchPhase::Ended, None);
+        assert_eq!(input.touches().len(), 2);
+        input.end_frame();
+        assert_eq!(input.touches().iter().map(|t| t.id).collect::<Vec<_>>(), vec![2]);
+        touch(&mut input, 2, TouchPhase::Cancelled, None);
+        input.end_frame();
+        assert!(input.touches().is_empty());
+    }
+
+    #[test]
+    fn pen_from_altitude() {
+        let mut input = InputState::new(1.0);
+        touch(&mut input, 1, TouchPhase::Started, None);
+        assert_eq!(input.pen(), None);
+        assert_eq!(input.touches()[0].kind, PointerKind::Finger);
+        touch(&mut input, 2, TouchPhase::Started, Some(0.8));
+        let pen = input.pen().unwrap();
+        assert_eq!((pen.id, pen.altitude, pen.force), (2, Some(0.8), Some(0.5)));
+        assert_eq!(pen.pos, [10.0, 20.0]);
+    }
+
+    #[test]
+    fn cursor_between_windows() {
+        let id = unsafe { WindowId::dummy() };
+        let rects = [(id, [0.0, 0.0, 100.0, 100.0]), (id, [100.0, 0.0, 50.0, 50.0])];
+        assert_eq!(window_at(&rects, [120.0, 10.0]), Some((id, [20.0, 10.0])));
+        assert_eq!(window_at(&rects, [120.0, 60.0]), None);
+        assert_eq!(cursor_target(id, [30.0, 40.0], &rects), Some((id, [30.0, 40.0])));
+        // held out of the window, the cursor goes to the one under it
+        assert_eq!(cursor_target(id, [110.0, 5.0], &rects), Some((id, [10.0, 5.0])));
+        assert_eq!(cursor_target(id, [-5.0, 5.0], &rects), None);
+        // without the window positions, it stays
+        assert_eq!(cursor_target(id, [-5.0, 5.0], &[]), Some((id, [-5.0, 5.0])));
+    }
+}
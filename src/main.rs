@@ -1,58 +1,214 @@
 //use gl;
-use glutin::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use glutin::event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
 use glutin::event_loop::{ControlFlow, EventLoop};
 use glutin::window::WindowId;
 use luminance::context::GraphicsContext;
-use luminance::pipeline::PipelineState;
-use luminance::render_state::RenderState;
+use luminance::framebuffer::{ColorSlot, DepthSlot, Framebuffer};
+use luminance::pipeline::{Builder, PipelineState};
 use luminance::shader::program::Program;
 use luminance::tess::{Mode, Tess, TessBuilder};
+use luminance::texture::{Dim2, Flat};
 use luminance_windowing::{WindowDim, WindowOpt};
 use std::collections::HashMap;
+use std::path::Path;
+use std::process::exit;
+use std::time::Instant;
+
+mod color;
+use color::{Color, Harmony};
+
+mod camera;
+use camera::{CamIface, Camera};
 
 mod winger;
 use winger::{CtxCurrWrapper, WinManager, WinSurface};
 
 mod in_utils;
-use in_utils::{TRIS_FIRST, TRIS_FULL, Semantics, TessMethod, new_nb};
+use in_utils::{
+    TEX_QUAD, THUMB_QUAD, TRIS_FIRST, TRIS_FULL,
+    DemoMode, Instance, Semantics, TessMethod, Vertex,
+    DemoRng, instance_grid, wave_instances, wave_verts
+};
+
+mod mesh;
+
+mod geometry;
+use geometry::{GeoKind, Geometry, GeometryDesc};
+
+mod texture;
+use texture::{RgbaTex, TexIface, TexImage, TexOpt};
+
+mod assets;
+use assets::Assets;
+
+mod postfx;
+use postfx::PostChain;
+
+mod share;
+use share::SharedTarget;
+
+mod bench;
+
+mod stream;
+use stream::{DynTess, RingTess};
+
+mod shapes;
+use shapes::{LineCap, LineJoin, ShapeBuilder};
+
+mod material;
+use material::{BlendMode, Cull, Material, MaterialError, Scissor};
+
+mod batch;
+use batch::{BatchKey, BatchRenderer, BatchStats, StyleId, TexId};
+
+mod shaping;
+use shaping::FontChain;
+
+mod text;
+use text::{SdfOpt, TextRenderer, TextStyle};
+
+mod gui;
+use gui::Ui;
+
+mod widgets;
+use widgets::{Align, Flex, Justify, NodeId, Widget, WidgetTree};
+
+mod theme;
+use theme::Theme;
+
+mod dnd;
+use dnd::{DndEvent, DragDrop, Payload};
+
+mod clipboard;
+use clipboard::{ClipError, ClipImage, Clipboard};
+
+mod bindings;
+use bindings::{Action, ActionMap};
+
+mod input;
+use input::{InputState, PointerKind};
+
+mod gamepad;
+use gamepad::{Gamepads, PadAxis, PadEvent, VirtualGamepad};
+
+#[cfg(feature = "nuklear")]
+mod nk;
+#[cfg(feature = "nuklear")]
+use nuklear::{nk_string, Flags, PanelFlags, Rect};
 
 const VS: &'static str = include_str!("../ressources/simple-vs.glsl");
 const FS: &'static str = include_str!("../ressources/simple-fs.glsl");
+const VS3: &'static str = include_str!("../ressources/simple3d-vs.glsl");
+const INST_VS: &'static str = include_str!("../ressources/inst-vs.glsl");
+const TEX_VS: &'static str = include_str!("../ressources/tex-vs.glsl");
+const TEX_FS: &'static str = include_str!("../ressources/tex-fs.glsl");
+
+/// Batch layer of the drop target frame, under the GUI.
+const DROP_LAYER: u16 = 30;
+
+/// Widgets of the status bar, to update them and tell the clicks apart.
+struct StatusBar {
+    status: NodeId,
+    mode: NodeId,
+    view: NodeId,
+    post: NodeId
+}
 
 struct WinData {
-    redraw: bool,
-    demo: TessMethod,
-    pub tesses: [Tess; 4],
+    demo: DemoMode,
+    pub tesses: [DynTess; 4],
     pub program: Program<Semantics, (), ()>,
-    pub bgcol: [f32; 4]
+    pub inst_tesses: [RingTess; 2],
+    pub inst_program: Program<Semantics, (), ()>,
+    pub batch: BatchRenderer,
+    batch_tex: TexId,
+    last_stats: BatchStats,
+    text: TextRenderer,
+    sdf_text: TextRenderer,
+    title_style: StyleId,
+    title: String,
+    ui: Ui,
+    show_ui: bool,
+    bg_hue: f32,
+    #[cfg(feature = "nuklear")]
+    nk: nk::NkBackend,
+    tree: WidgetTree,
+    status_bar: StatusBar,
+    fonts: FontChain,
+    themes: Vec<Theme>,
+    theme: usize,
+    /// Text renderers of the theme fonts, by font name.
+    ui_texts: Vec<(String, TextRenderer)>,
+    ui_text: usize,
+    base_tris: [Vertex; 6],
+    base_insts: Vec<Instance>,
+    animate: bool,
+    pub bgcol: Color,
+    srgb: bool,
+    rng: DemoRng,
+    view3d: bool,
+    material3d: Material,
+    pub camera: Camera,
+    pub scene: [Tess; 4],
+    pub geometries: Vec<Geometry>,
+    pub program3d: Program<Semantics, (), CamIface>,
+    pub quad: Tess,
+    pub texture: RgbaTex,
+    pub tex_program: Program<Semantics, (), TexIface>,
+    pub post: PostChain,
+    post_preset: usize,
+    pub thumb: Tess,
+    preview_of: Option<WindowId>,
+    show_preview: bool,
+    /// What a drag hovering the window would drop.
+    drop_hover: Option<Payload>,
+    clipboard: Clipboard,
+    /// Copy the next frame to the clipboard.
+    copy_requested: bool,
+    actions: ActionMap,
+    input: InputState,
+    /// The scene changed, the widget tree keeping its own paint state.
+    redraw: bool
 }
 
 impl WinData {
-    fn new(surface: &mut WinSurface,first: bool) -> WinData {
+    fn new(
+        surface: &mut WinSurface,
+        index: usize,
+        assets: &Assets,
+        mut rng: DemoRng,
+        clipboard: Clipboard
+    ) -> WinData {
         //
-        let tris = if first { TRIS_FIRST } else { TRIS_FULL };
+        let tris = if index == 0 { TRIS_FIRST } else { TRIS_FULL };
         //let tris = TRIS_FIRST;
+        // the vertex colours are sRGB, linear for an sRGB framebuffer, and the
+        // images uploaded as sRGB for gl to decode them
+        let srgb = surface.is_srgb();
+        let mut tri_verts = tris.tri_verts;
+        for vert in tri_verts.iter_mut() { *vert = vert.for_fb(srgb); }
+        let tri_deint_col_verts: Vec<_> = tris.tri_deint_col_verts.iter().map(|c| c.for_fb(srgb)).collect();
         //
         let direct_tris = TessBuilder::new(surface)
-            .add_vertices(tris.tri_verts)
+            .add_vertices(tri_verts)
             .set_mode(Mode::Triangle)
             .build()
             .unwrap();
         let indexed_tris = TessBuilder::new(surface)
-            .add_vertices(tris.tri_verts)
+            .add_vertices(tri_verts)
             .set_indices(tris.tri_inds)
             .set_mode(Mode::Triangle)
             .build()
             .unwrap();
         let direct_deint_tris = TessBuilder::new(surface)
             .add_vertices(tris.tri_deint_pos_verts)
-            .add_vertices(tris.tri_deint_col_verts)
+            .add_vertices(&tri_deint_col_verts[..])
             .set_mode(Mode::Triangle)
             .build()
             .unwrap();
         let indexed_deint_tris = TessBuilder::new(surface)
             .add_vertices(tris.tri_deint_pos_verts)
-            .add_vertices(tris.tri_deint_col_verts)
+            .add_vertices(&tri_deint_col_verts[..])
             .set_indices(tris.tri_inds)
             .set_mode(Mode::Triangle)
             .build()
@@ -62,58 +218,833 @@ impl WinData {
             .expect("program creation")
             .ignore_warnings();
         //
+        let instances: Vec<_> = instance_grid(&mut rng).iter().map(|inst| inst.for_fb(srgb)).collect();
+        let inst_tris = RingTess::new(3, || TessBuilder::new(surface)
+            .add_vertices(tri_verts)
+            .add_instances(&instances[..])
+            .set_mode(Mode::Triangle)
+            .build()
+            .unwrap());
+        let indexed_inst_tris = RingTess::new(3, || TessBuilder::new(surface)
+            .add_vertices(tri_verts)
+            .add_instances(&instances[..])
+            .set_indices(tris.tri_inds)
+            .set_mode(Mode::Triangle)
+            .build()
+            .unwrap());
+        let inst_program = Program::<Semantics, (), ()>::from_strings(None, INST_VS, None, FS)
+            .expect("program inst creation")
+            .ignore_warnings();
+        //
+        let scene = assets.mesh.for_fb(srgb).to_tesses(surface).expect("mesh tesses creation");
+        let geometries = assets.geometries.iter()
+            .map(|desc| desc.to_geometry(surface, srgb).expect("geometry tess creation"))
+            .collect();
+        let program3d = Program::<Semantics, (), CamIface>::from_strings(None, VS3, None, FS)
+            .expect("program 3d creation")
+            .ignore_warnings();
+        let size = surface.size();
+        let [w, h] = size;
+        let mut camera = Camera::new(1.0);
+        camera.set_aspect(w, h);
         //
-        WinData {
-            redraw: false,
-            demo: TessMethod::Direct,
-            tesses: [direct_tris, indexed_tris, direct_deint_tris, indexed_deint_tris],
+        let quad = TessBuilder::new(surface)
+            .add_vertices(TEX_QUAD)
+            .set_mode(Mode::TriangleFan)
+            .build()
+            .unwrap();
+        let texture = assets.image.upload(surface, &assets.tex_opt, srgb).expect("texture creation");
+        let tex_program = Program::<Semantics, (), TexIface>::from_strings(None, TEX_VS, None, TEX_FS)
+            .expect("program tex creation")
+            .ignore_warnings();
+        //
+        let post = PostChain::new(surface, size).expect("post chain creation");
+        let mut batch = BatchRenderer::new(surface);
+        let batch_tex = batch.add_texture(
+            assets.image.upload(surface, &assets.tex_opt, srgb).expect("batch texture creation")
+        );
+        let text = TextRenderer::new(surface, &mut batch, assets.fonts.clone(), srgb)
+            .expect("text renderer creation");
+        let sdf_text = TextRenderer::new_sdf(surface, &mut batch, assets.fonts.clone(), srgb, SdfOpt::default())
+            .expect("sdf text renderer creation");
+        let title_style = sdf_text.add_style(&mut batch, TextStyle {
+            outline_color: Color::named("navy").unwrap(),
+            outline_width: 0.15,
+            shadow_color: Color::BLACK.with_alpha(0.6),
+            shadow_offset: [0.003, 0.003],
+            shadow_softness: 0.1
+        });
+        #[cfg(feature = "nuklear")]
+        let nk = nk::NkBackend::new(surface, &mut batch, 16.0, srgb).expect("nuklear backend creation");
+        let thumb = TessBuilder::new(surface)
+            .add_vertices(THUMB_QUAD)
+            .set_mode(Mode::TriangleFan)
+            .build()
+            .unwrap();
+        //
+        let (tree, status_bar) = WinData::status_tree(size, srgb);
+        let mut win_data = WinData {
+            demo: DemoMode::Flat(TessMethod::Direct),
+            tesses: [
+                DynTess::new(direct_tris),
+                DynTess::new(indexed_tris),
+                DynTess::new(direct_deint_tris),
+                DynTess::new(indexed_deint_tris)
+            ],
             program,
-            bgcol: [0.0, 0.0, 0.0, 1.0]
+            inst_tesses: [inst_tris, indexed_inst_tris],
+            inst_program,
+            batch,
+            batch_tex,
+            last_stats: BatchStats::default(),
+            text,
+            sdf_text,
+            title_style,
+            title: "Nikut shapes".to_string(),
+            ui: Ui::new(srgb),
+            show_ui: true,
+            bg_hue: 0.0,
+            #[cfg(feature = "nuklear")]
+            nk,
+            tree,
+            status_bar,
+            fonts: assets.fonts.clone(),
+            themes: assets.themes.clone(),
+            theme: 0,
+            ui_texts: Vec::new(),
+            ui_text: 0,
+            base_tris: tri_verts,
+            base_insts: instances,
+            animate: false,
+            bgcol: Color::BLACK,
+            srgb,
+            rng,
+            view3d: false,
+            material3d: Material::solid().checked().expect("3D material"),
+            camera,
+            scene,
+            geometries,
+            program3d,
+            quad,
+            texture,
+            tex_program,
+            post,
+            post_preset: 0,
+            thumb,
+            preview_of: None,
+            show_preview: true,
+            drop_hover: None,
+            clipboard,
+            copy_requested: false,
+            actions: ActionMap::new(assets.bindings.window(index)),
+            input: InputState::new(surface.scale_factor()),
+            redraw: true
+        };
+        win_data.update_status();
+        win_data.ui.set_clipboard(win_data.clipboard.clone());
+        win_data.set_theme(surface, assets.theme);
+        win_data
+    }
+
+    /// A bar at the bottom of the window: the state on the left, the buttons
+    /// switching it on the right.
+    fn status_tree(size: [u32; 2], srgb: bool) -> (WidgetTree, StatusBar) {
+        let mut tree = WidgetTree::new(Flex::column().justify(Justify::End), size, srgb);
+        let bar = tree.add(tree.root(), Widget::panel(Flex::row().align(Align::Center).gap(8.0)));
+        let status = tree.add(bar, Widget::label("").flex(Flex::default().grow(1.0)));
+        let ids = StatusBar {
+            status,
+            mode: tree.add(bar, Widget::button("Mode")),
+            view: tree.add(bar, Widget::button("3D")),
+            post: tree.add(bar, Widget::button("Post"))
+        };
+        (tree, ids)
+    }
+
+    pub fn theme(&self) -> usize { self.theme }
+
+    /// Restyle the GUI and the widget tree, the ctx must be current: a new
+    /// theme font needs its text renderer.
+    pub fn set_theme(&mut self, surface: &mut WinSurface, index: usize) {
+        let index = index % self.themes.len();
+        let theme = &self.themes[index];
+        let font = theme.font_name();
+        self.ui_text = match self.ui_texts.iter().position(|(name, _)| name == font) {
+            Some(found) => found,
+            None => {
+                let fonts = theme.fonts(&self.fonts).unwrap_or_else(|e| {
+                    eprintln!("{}, back to the default fonts", e);
+                    self.fonts.clone()
+                });
+                let text = TextRenderer::new(surface, &mut self.batch, fonts, self.srgb)
+                    .expect("theme text renderer creation");
+                self.ui_texts.push((font.to_string(), text));
+                self.ui_texts.len() - 1
+            }
+        };
+        self.ui.style = theme.ui_style();
+        self.tree.set_style(theme.tree_style());
+        self.theme = index;
+        self.update_status();
+        self.invalidate();
+    }
+
+    /// Act on the status bar buttons clicked since the last call.
+    pub fn apply_clicks(&mut self) {
+        while let Some(id) = self.tree.take_clicked() {
+            if id == self.status_bar.mode { self.next_mode(); }
+            else if id == self.status_bar.view { self.toggle_3d(); }
+            else if id == self.status_bar.post { self.next_post(); }
+            self.update_status();
+            self.invalidate();
+        }
+    }
+
+    /// Show the window state and the pointer one in the status bar, to call
+    /// when they change.
+    pub fn update_status(&mut self) {
+        let status = if self.is_3d() {
+            format!("3D, {:?} camera, post preset {}", self.camera.mode(), self.post_preset)
+        }
+        else { format!("{:?}, post preset {}", self.demo, self.post_preset) };
+        let status = format!("{} | {}", status, self.pointer_status());
+        self.tree.set_text(self.status_bar.status, &status);
+        // the buttons of the switched on states stay lit
+        let on = Some(self.tree.style().buttons[2]);
+        self.tree.set_background(self.status_bar.view, if self.is_3d() { on } else { None });
+        self.tree.set_background(self.status_bar.post, if self.post_preset != 0 { on } else { None });
+    }
+
+    /// The scene changed, the window needs a redraw.
+    pub fn invalidate(&mut self) { self.redraw = true; }
+
+    pub fn need_redraw(&self) -> bool { self.redraw || self.tree.needs_redraw() }
+
+    pub fn redrawed(&mut self) {
+        self.redraw = false;
+        self.tree.painted();
+    }
+
+    pub fn get_mode(&self) -> DemoMode { self.demo }
+
+    pub fn is_3d(&self) -> bool { self.view3d }
+
+    pub fn toggle_3d(&mut self) { self.view3d = !self.view3d; }
+
+    /// Tesses of the geometries loaded from files, drawn with the `kind` program.
+    pub fn geometries_of(&self, kind: GeoKind) -> impl Iterator<Item = &Tess> {
+        self.geometries.iter().filter(move |geo| geo.kind == kind).map(|geo| &geo.tess)
+    }
+
+    /// Cycle the culled faces of the 3D scene: back, front, none.
+    pub fn next_cull(&mut self) -> Result<Option<Cull>, MaterialError> {
+        let cull = match self.material3d.cull {
+            Some(Cull::Back) => Some(Cull::Front),
+            Some(Cull::Front) => None,
+            None => Some(Cull::Back)
+        };
+        self.material3d = self.material3d.cull(cull).checked()?;
+        Ok(cull)
+    }
+
+    pub fn scene_tess(&self) -> &Tess {
+        match self.demo {
+            DemoMode::Flat(TessMethod::Direct) | DemoMode::Instanced => &self.scene[0],
+            DemoMode::Flat(TessMethod::Indexed) | DemoMode::IndexedInstanced
+            | DemoMode::Textured | DemoMode::Shapes => &self.scene[1],
+            DemoMode::Flat(TessMethod::DirectDeinter) => &self.scene[2],
+            DemoMode::Flat(TessMethod::IndexedDeinter) => &self.scene[3]
+        }
+    }
+
+    /// Program and tess of the flat (2D, untextured) demo modes, none for
+    /// the textured and shapes ones.
+    pub fn flat_draw(&self, mode: DemoMode) -> Option<(&Program<Semantics, (), ()>, &Tess)> {
+        Some(match mode {
+            DemoMode::Flat(TessMethod::Direct) => (&self.program, self.tesses[0].tess()),
+            DemoMode::Flat(TessMethod::Indexed) => (&self.program, self.tesses[1].tess()),
+            DemoMode::Flat(TessMethod::DirectDeinter) => (&self.program, self.tesses[2].tess()),
+            DemoMode::Flat(TessMethod::IndexedDeinter) => (&self.program, self.tesses[3].tess()),
+            DemoMode::Instanced => (&self.inst_program, self.inst_tesses[0].current()),
+            DemoMode::IndexedInstanced => (&self.inst_program, self.inst_tesses[1].current()),
+            DemoMode::Textured | DemoMode::Shapes => return None
+        })
+    }
+
+    /// Shapes laid out in pixels, for the current window size.
+    fn demo_shapes(size: [u32; 2], srgb: bool) -> ShapeBuilder {
+        let line = [[40.0, 300.0], [120.0, 220.0], [200.0, 330.0], [280.0, 240.0]];
+        let triad = Color::named("coral").unwrap().palette(Harmony::Triadic);
+        ShapeBuilder::new()
+            .viewport(size)
+            .srgb(srgb)
+            .color(Color::from_hex("#3c3c50").unwrap())
+            .rounded_rect([20.0, 20.0], [300.0, 160.0], 24.0)
+            .color(triad[0])
+            .rect([40.0, 40.0], [120.0, 50.0])
+            .color(triad[1])
+            .circle([240.0, 100.0], 50.0)
+            .color(triad[2])
+            .arc([420.0, 110.0], 60.0, 0.0, 4.5, 16.0)
+            .color(Color::from_hsl(120.0, 0.6, 0.65))
+            .polyline(&line, 14.0, LineJoin::Round, LineCap::Round, false)
+            .color(Color::named("magenta").unwrap())
+            .polyline(&line, 4.0, LineJoin::Miter { limit: 4.0 }, LineCap::Butt, false)
+            .color(Color::named("silver").unwrap())
+            .polygon(&[
+                [540.0, 200.0], [700.0, 200.0], [700.0, 360.0],
+                [620.0, 280.0], [540.0, 360.0]
+            ])
+    }
+
+    /// The settings panel, its widgets act on the window right away.
+    fn build_ui(&mut self, size: [u32; 2]) {
+        let ui = &mut self.ui;
+        ui.begin(size);
+        ui.begin_panel("Settings", [size[0] as f32 - 240.0, 12.0], 228.0);
+        ui.label(&format!("Mode: {:?}", self.demo));
+        let next_mode = ui.button("Next mode");
+        let mut changed = next_mode;
+        changed |= ui.checkbox("Animate", &mut self.animate);
+        changed |= ui.checkbox("3D view", &mut self.view3d);
+        if ui.slider("Background hue", &mut self.bg_hue, 0.0, 360.0) {
+            self.bgcol = Color::from_hsv(self.bg_hue, 0.5, 0.3);
+            changed = true;
+        }
+        changed |= ui.text_field("Title", &mut self.title);
+        ui.end_panel();
+        ui.end(&mut self.batch, &mut self.ui_texts[self.ui_text].1);
+        if next_mode { self.next_mode(); }
+        if changed {
+            self.update_status();
+            self.invalidate();
+        }
+    }
+
+    /// The Nuklear twin of the settings panel, to check both backends side
+    /// by side.
+    #[cfg(feature = "nuklear")]
+    fn build_nk(&mut self, size: [u32; 2]) {
+        let (mut next_mode, mut hue) = (false, self.bg_hue / 360.0);
+        self.nk.frame(size, |ctx| {
+            let flags = PanelFlags::Border as Flags | PanelFlags::Movable as Flags | PanelFlags::Title as Flags;
+            if ctx.begin(nk_string!("Nuklear"), Rect { x: 12.0, y: 80.0, w: 220.0, h: 140.0 }, flags) {
+                ctx.layout_row_dynamic(28.0, 1);
+                next_mode = ctx.button_text("Next mode");
+                ctx.slider_float(0.0, &mut hue, 1.0, 0.01);
+            }
+            ctx.end();
+        });
+        self.nk.submit(&mut self.batch);
+        if next_mode { self.next_mode(); }
+        if (hue * 360.0 - self.bg_hue).abs() > 0.01 {
+            self.bg_hue = hue * 360.0;
+            self.bgcol = Color::from_hsv(self.bg_hue, 0.5, 0.3);
+        }
+        if next_mode {
+            self.update_status();
+            self.invalidate();
+        }
+    }
+
+    pub fn toggle_ui(&mut self) { self.show_ui = !self.show_ui; }
+
+    /// In 2D, the wheel turns the background hue.
+    pub fn scroll(&mut self, lines: f32) {
+        if self.is_3d() || lines.abs() <= 0.0 { return; }
+        self.bg_hue = (self.bg_hue + lines * 10.0).rem_euclid(360.0);
+        self.bgcol = Color::from_hsv(self.bg_hue, 0.5, 0.3);
+        self.invalidate();
+    }
+
+    /// What a drag from the window carries: the title in the shapes mode, the
+    /// background colour otherwise.
+    pub fn drag_payload(&self) -> Payload {
+        match self.demo {
+            DemoMode::Shapes if !self.is_3d() => Payload::Text(self.title.clone()),
+            _ => Payload::Color(self.bgcol)
+        }
+    }
+
+    /// Drag and drop event of the window, the ctx must be current for the
+    /// dropped files.
+    pub fn handle_dnd(&mut self, surface: &mut WinSurface, event: DndEvent) {
+        match event {
+            DndEvent::Enter { payload, .. } | DndEvent::Over { payload, .. } => self.drop_hover = Some(payload),
+            DndEvent::Leave => self.drop_hover = None,
+            DndEvent::Drop { payload, .. } => {
+                self.drop_hover = None;
+                match payload {
+                    Payload::Color(color) => self.bgcol = color,
+                    Payload::Text(text) => self.title = text,
+                    Payload::Files(paths) => for path in paths {
+                        if let Err(e) = self.load_file(surface, &path) { eprintln!("{}", e); }
+                    }
+                }
+            }
+        }
+        self.invalidate();
+    }
+
+    /// Copy the window content to the clipboard, at the next frame.
+    pub fn request_copy(&mut self) {
+        self.copy_requested = true;
+        self.invalidate();
+    }
+
+    /// To call after drawing a frame, before the swap: copy it if requested.
+    pub fn copy_frame(&mut self, size: [u32; 2]) {
+        if !self.copy_requested { return; }
+        self.copy_requested = false;
+        match ClipImage::read_gl(size).and_then(|image| self.clipboard.set_image(&image)) {
+            Ok(()) => println!("Frame of {}x{} copied", size[0], size[1]),
+            Err(e) => eprintln!("{}", e)
+        }
+    }
+
+    /// Paste an image as the texture, or a text as the background colour if it
+    /// is one, as the title otherwise. The ctx must be current.
+    pub fn paste(&mut self, surface: &mut WinSurface) {
+        match self.clipboard.get_image() {
+            Ok(image) => match image.to_tex_image().upload(surface, &TexOpt::default(), self.srgb) {
+                Ok(texture) => self.texture = texture,
+                Err(e) => eprintln!("error with the pasted image: {:?}", e)
+            },
+            Err(ClipError::Empty) => match self.clipboard.get_text() {
+                Ok(text) => match text.trim().parse::<Color>() {
+                    Ok(color) => self.bgcol = color,
+                    Err(_) => self.title = text.lines().next().unwrap_or_default().to_string()
+                },
+                Err(e) => eprintln!("{}", e)
+            },
+            Err(e) => eprintln!("{}", e)
+        }
+        self.invalidate();
+    }
+
+    /// Add a dropped geometry file, or replace the texture with a dropped image.
+    fn load_file(&mut self, surface: &mut WinSurface, path: &Path) -> Result<(), String> {
+        let ext = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "ron" | "json" => {
+                let desc = GeometryDesc::load(path).map_err(|e| e.to_string())?;
+                self.geometries.push(desc.to_geometry(surface, self.srgb).map_err(|e| e.to_string())?);
+            }
+            "png" | "jpg" | "jpeg" => {
+                let image = TexImage::load(path).map_err(|e| e.to_string())?;
+                self.texture = image.upload(surface, &TexOpt::default(), self.srgb).map_err(|e| e.to_string())?;
+            }
+            _ => return Err(format!("unknown dropped file: {:?}", path))
+        }
+        Ok(())
+    }
+
+    /// The pointer state, for the status bar.
+    fn pointer_status(&self) -> String {
+        let input = &self.input;
+        let mut parts = Vec::new();
+        match (input.cursor(), input.cursor_logical()) {
+            (Some(p), Some(l)) => parts.push(format!(
+                "cursor {:.0}x{:.0} ({:.0}x{:.0} at x{})", p[0], p[1], l[0], l[1], input.scale()
+            )),
+            _ => parts.push("cursor out".to_string())
+        }
+        if !input.buttons().is_empty() { parts.push(format!("{:?}", input.buttons())); }
+        let fingers = input.touches().iter().filter(|t| t.is_active() && t.kind == PointerKind::Finger).count();
+        if fingers > 0 { parts.push(format!("{} touches", fingers)); }
+        if let Some(pen) = input.pen() {
+            parts.push(format!("pen {:.0}x{:.0}, pressure {:.2}", pen.pos[0], pen.pos[1], pen.force.unwrap_or(1.0)));
+        }
+        parts.join(", ")
+    }
+
+    /// Submit the overlays to the batch renderer and upload them: the GUI and
+    /// the status bar, plus the shapes and sprites in the shapes mode.
+    pub fn prepare_batch(&mut self, surface: &mut WinSurface) {
+        let size = surface.size();
+        if !self.is_3d() { self.submit_2d(surface); }
+        let text = &mut self.ui_texts[self.ui_text].1;
+        self.tree.layout(&mut |s, px| text.layout(s, px, None).size);
+        self.tree.draw(&mut self.batch, text);
+        if self.drop_hover.is_some() {
+            let (w, h) = (size[0] as f32, size[1] as f32);
+            let frame = ShapeBuilder::new()
+                .viewport(size)
+                .srgb(self.srgb)
+                .color(self.ui.style.accent)
+                .polyline(
+                    &[[2.0, 2.0], [w - 2.0, 2.0], [w - 2.0, h - 2.0], [2.0, h - 2.0]],
+                    4.0, LineJoin::Miter { limit: 4.0 }, LineCap::Butt, true
+                );
+            self.batch.shape(BatchKey { layer: DROP_LAYER, ..BatchKey::default() }, frame);
+        }
+        if self.show_ui {
+            self.build_ui(size);
+            #[cfg(feature = "nuklear")]
+            self.build_nk(size);
+        }
+        let skipped = self.text.flush(&self.batch) | self.ui_texts[self.ui_text].1.flush(&self.batch);
+        if skipped { self.invalidate(); }
+        self.batch.prepare(surface, size);
+        let stats = self.batch.stats();
+        if stats != self.last_stats {
+            println!(
+                "Batch: {} submissions, {} draw calls, {} vertices",
+                stats.submissions, stats.draw_calls, stats.vertices
+            );
+            self.last_stats = stats;
+        }
+    }
+
+    /// The sprites and discs alternate textures and blend modes on purpose:
+    /// blended, they keep their order, each change of state being a draw call.
+    fn submit_2d(&mut self, surface: &WinSurface) {
+        let size = surface.size();
+        if let DemoMode::Shapes = self.demo {
+            self.batch.shape(BatchKey::default(), WinData::demo_shapes(surface.size(), self.srgb));
+            let white = Color::WHITE.vertex_rgb(self.srgb);
+            let discs = Color::named("navy").unwrap().gradient(Color::named("lime").unwrap(), 8);
+            for i in 0..8 {
+                let x = -0.9 + i as f32 * 0.22;
+                let blend = if i % 2 == 0 { BlendMode::Alpha } else { BlendMode::Additive };
+                let sprite = BatchKey { layer: 1, texture: self.batch_tex, blend, ..BatchKey::default() };
+                self.batch.quad(sprite, [x, -0.9], [0.2, 0.4], [[0.0, 0.0], [1.0, 1.0]], white);
+                let disc = ShapeBuilder::new().srgb(self.srgb).color(discs[i]).circle([x + 0.1, -0.3], 0.08);
+                self.batch.shape(BatchKey { layer: 1, blend, ..BatchKey::default() }, disc);
+            }
+            // a clipped panel, the disc overflows it on purpose
+            let clip = Scissor::from_top_left([size[0] / 2, 20], [size[0] / 3, size[1] / 4], size[1]);
+            let panel = BatchKey { layer: 2, blend: BlendMode::Premultiplied, clip: Some(clip), ..BatchKey::default() };
+            let panel_shapes = ShapeBuilder::new()
+                .srgb(self.srgb)
+                .color([40, 40, 60])
+                .rect([-1.0, -1.0], [2.0, 2.0])
+                .color(Color::named("orange").unwrap())
+                .circle([0.5, 0.8], 0.4);
+            self.batch.shape(panel, panel_shapes);
+            // the title follows the window height, the SDF keeps it sharp
+            let title_px = (size[1] as f32 / 6.0).max(8.0);
+            let title = self.sdf_text.layout(&self.title, title_px, None);
+            let title_pos = [(size[0] as f32 - title.size[0]) / 2.0, size[1] as f32 - title.size[1] * 1.5];
+            self.sdf_text.draw_layout(
+                &mut self.batch, size, title_pos, Color::named("yellow").unwrap(), self.title_style, &title
+            );
+            if self.sdf_text.flush(&self.batch) { self.invalidate(); }
+            // the captions are small, the bitmap glyphs suit them
+            self.text.draw_text(
+                &mut self.batch, size, [8.0, 8.0], 14.0, Color::WHITE,
+                "Alpha and additive sprites, a clipped panel"
+            );
+        }
+    }
+
+    pub fn toggle_animate(&mut self) { self.animate = !self.animate; }
+
+    /// Animated only for the modes with an interleaved buffer to map.
+    pub fn is_animated(&self) -> bool {
+        self.animate && !self.is_3d() && match self.demo {
+            DemoMode::Flat(TessMethod::Direct) | DemoMode::Flat(TessMethod::Indexed)
+            | DemoMode::Instanced | DemoMode::IndexedInstanced => true,
+            DemoMode::Flat(TessMethod::DirectDeinter) | DemoMode::Flat(TessMethod::IndexedDeinter)
+            | DemoMode::Textured | DemoMode::Shapes => false
         }
     }
 
-    pub fn toggle(&mut self) { self.redraw = true; }
+    /// Rewrite the buffers of the current mode for the time `t`, the window
+    /// ctx must be the current one.
+    pub fn stream(&mut self, t: f32) {
+        if !self.is_animated() { return; }
+        let (base_tris, base_insts) = (&self.base_tris, &self.base_insts);
+        let res = match self.demo {
+            DemoMode::Flat(TessMethod::Direct) => self.tesses[0]
+                .update(|verts: &mut [Vertex]| wave_verts(base_tris, t, verts)),
+            DemoMode::Flat(TessMethod::Indexed) => self.tesses[1]
+                .update(|verts: &mut [Vertex]| wave_verts(base_tris, t, verts)),
+            DemoMode::Instanced => self.inst_tesses[0].next_mut()
+                .update_instances(|insts: &mut [Instance]| wave_instances(base_insts, t, insts)),
+            DemoMode::IndexedInstanced => self.inst_tesses[1].next_mut()
+                .update_instances(|insts: &mut [Instance]| wave_instances(base_insts, t, insts)),
+            _ => Ok(())
+        };
+        if let Err(e) = res { eprintln!("error with the buffer streaming: {:?}", e); }
+    }
+
+    pub fn next_post(&mut self) {
+        let presets = postfx::presets();
+        self.post_preset = (self.post_preset + 1) % presets.len();
+        self.post.set_effects(presets[self.post_preset].clone());
+    }
 
-    pub fn need_redraw(&self) -> bool { self.redraw }
+    pub fn preview(&self) -> Option<WindowId> {
+        if self.show_preview { self.preview_of } else { None }
+    }
 
-    pub fn redrawed(&mut self) { self.redraw = false; }
+    pub fn toggle_preview(&mut self) { self.show_preview = !self.show_preview; }
 
-    pub fn get_mode(&self) -> TessMethod { self.demo }
+    /// Draw the shared texture of another window over the current content.
+    pub fn render_preview(
+        &self,
+        builder: &Builder<WinSurface>,
+        fb: &Framebuffer<Flat, Dim2, (), ()>,
+        shared: &SharedTarget
+    ) {
+        shared.wait();
+        let pipe_state = PipelineState::default().enable_clear_color(false);
+        builder.pipeline(fb, &pipe_state, |pipeline, mut shd_gate| {
+            let bound_tex = pipeline.bind_texture(shared.fb().color_slot());
+            shd_gate.shade(&self.tex_program, |iface, mut rdr_gate| {
+                iface.tex.update(&bound_tex);
+                Material::default().scoped(|state| {
+                    rdr_gate.render(&state, |mut tess_gate| {
+                        tess_gate.render(&self.thumb);
+                    });
+                });
+            });
+        });
+    }
+
+    /// The scene with the batched overlays over it.
+    pub fn render<CS, DS>(
+        &self,
+        builder: &Builder<WinSurface>,
+        fb: &Framebuffer<Flat, Dim2, CS, DS>
+    ) where CS: ColorSlot<Flat, Dim2>, DS: DepthSlot<Flat, Dim2> {
+        self.render_scene(builder, fb);
+        self.batch.render(builder, fb);
+    }
+
+    /// The scene only, the batch being laid out for the window size.
+    pub fn render_scene<CS, DS>(
+        &self,
+        builder: &Builder<WinSurface>,
+        fb: &Framebuffer<Flat, Dim2, CS, DS>
+    ) where CS: ColorSlot<Flat, Dim2>, DS: DepthSlot<Flat, Dim2> {
+        let pipe_state = PipelineState::default().set_clear_color(self.bgcol.clear_color(self.srgb));
+        if self.is_3d() {
+            builder.pipeline(fb, &pipe_state, |_, mut shd_gate| {
+                shd_gate.shade(&self.program3d, |iface, mut rdr_gate| {
+                    iface.projection.update(self.camera.projection_matrix());
+                    iface.view.update(self.camera.view_matrix());
+                    self.material3d.scoped(|state| {
+                        rdr_gate.render(&state, |mut tess_gate| {
+                            tess_gate.render(self.scene_tess());
+                            for geo in self.geometries_of(GeoKind::Mesh) { tess_gate.render(geo); }
+                        });
+                    });
+                });
+            });
+        }
+        else if let DemoMode::Textured = self.get_mode() {
+            builder.pipeline(fb, &pipe_state, |pipeline, mut shd_gate| {
+                let bound_tex = self.texture.bind(&pipeline);
+                shd_gate.shade(&self.tex_program, |iface, mut rdr_gate| {
+                    iface.tex.update(&bound_tex);
+                    rdr_gate.render(&Material::default().render_state(), |mut tess_gate| {
+                        tess_gate.render(&self.quad);
+                        for geo in self.geometries_of(GeoKind::Textured) { tess_gate.render(geo); }
+                    });
+                });
+            });
+        }
+        else if let Some((program, tess)) = self.flat_draw(self.get_mode()) {
+            builder.pipeline(fb, &pipe_state, |_, mut shd_gate| {
+                shd_gate.shade(program, |_, mut rdr_gate| {
+                    //
+                    rdr_gate.render(&Material::default().render_state(), |mut tess_gate| {
+                        tess_gate.render(tess);
+                    });
+                    //
+                });
+                // the loaded geometries aren't instanced, they need the plain program
+                shd_gate.shade(&self.program, |_, mut rdr_gate| {
+                    rdr_gate.render(&Material::default().render_state(), |mut tess_gate| {
+                        for geo in self.geometries_of(GeoKind::Flat) { tess_gate.render(geo); }
+                    });
+                });
+            });
+        }
+        else {
+            // the shapes mode, clear only, the batch draws over it
+            builder.pipeline(fb, &pipe_state, |_, _| {});
+        }
+    }
 
     pub fn next_mode(&mut self) {
-        self.demo = match self.demo {
-            TessMethod::Direct => TessMethod::Indexed,
-            TessMethod::Indexed => TessMethod::DirectDeinter,
-            TessMethod::DirectDeinter => TessMethod::IndexedDeinter,
-            TessMethod::IndexedDeinter => TessMethod::Direct
+        self.demo = self.demo.next();
+    }
+}
+
+/// Give a cursor move of `window` to the window under the pointer, another
+/// one when a held button keeps the cursor in `window` out of it.
+fn route_cursor(
+    win_manager: &WinManager,
+    win_datas: &mut HashMap<WindowId, WinData>,
+    window: WindowId,
+    pos: [f64; 2]
+) {
+    let target = input::cursor_target(window, pos, &win_manager.screen_rects());
+    for (win_id, win_data) in win_datas.iter_mut() {
+        match target {
+            Some((target, pos)) if target == *win_id => win_data.input.set_cursor(Some(pos)),
+            _ => win_data.input.set_cursor(None)
         }
     }
 }
 
+/// Deliver drag and drop events to their windows.
+fn dispatch_dnd(
+    win_manager: &mut WinManager,
+    win_datas: &mut HashMap<WindowId, WinData>,
+    events: Vec<(WindowId, DndEvent)>
+) {
+    for (win_id, event) in events {
+        if let Some(win_data) = win_datas.get_mut(&win_id) {
+            if let Ok(surface) = win_manager.get_current(win_id) { win_data.handle_dnd(surface, event); }
+        }
+    }
+}
+
+/// Run an action of the window `window_id`.
+fn run_action(
+    win_manager: &mut WinManager,
+    win_datas: &mut HashMap<WindowId, WinData>,
+    shared_targets: &mut HashMap<WindowId, SharedTarget>,
+    dnd: &mut DragDrop,
+    window_id: WindowId,
+    action: Action
+) {
+    if action == Action::Close {
+        // release the GL objects while the window ctx is still alive
+        if win_manager.get_current(window_id).is_ok() {
+            shared_targets.remove(&window_id);
+            win_datas.remove(&window_id);
+        }
+        win_manager.remove_window(window_id);
+        dispatch_dnd(win_manager, win_datas, dnd.remove_window(window_id));
+        return;
+    }
+    let surface = win_manager.get_current(window_id).unwrap();
+    let win_data = win_datas.get_mut(&window_id).unwrap();
+    match action {
+        Action::NextMode => {
+            win_data.next_mode();
+            println!("Switch mode for win {:?}", &window_id);
+        }
+        Action::Toggle3d => win_data.toggle_3d(),
+        Action::ToggleProjection => win_data.camera.projection = win_data.camera.projection.toggle(),
+        Action::ToggleCamera => {
+            win_data.camera.toggle_mode();
+            println!("Camera {:?} for win {:?}", win_data.camera.mode(), &window_id);
+        }
+        Action::NextCull => match win_data.next_cull() {
+            Ok(cull) => println!("Culling {:?} for win {:?}", cull, &window_id),
+            Err(e) => eprintln!("culling error for win {:?}: {:?}", &window_id, e)
+        },
+        Action::NextPost => {
+            win_data.next_post();
+            println!("Post effects {:?} for win {:?}", win_data.post.effects(), &window_id);
+        }
+        Action::TogglePreview => win_data.toggle_preview(),
+        Action::Bench => bench::report(&bench::run(surface, win_data, 60)),
+        Action::ToggleAnimate => win_data.toggle_animate(),
+        Action::NextTheme => {
+            win_data.set_theme(surface, win_data.theme() + 1);
+            let name = &win_data.themes[win_data.theme()].name;
+            println!("Theme {} for win {:?}", name, &window_id);
+        }
+        Action::NextThemeAll => {
+            // the next theme of this window, for all of them
+            let next = win_data.theme() + 1;
+            for (win_id, win_data) in win_datas.iter_mut() {
+                win_data.set_theme(win_manager.get_current(*win_id).unwrap(), next);
+            }
+            let win_data = &win_datas[&window_id];
+            let name = &win_data.themes[win_data.theme()].name;
+            println!("Theme {} for all the windows", name);
+        }
+        Action::ToggleUi => win_data.toggle_ui(),
+        Action::Copy => win_data.request_copy(),
+        Action::Paste => win_data.paste(surface),
+        Action::RandomBackground =>
+            win_data.bgcol = Color::rgb(win_data.rng.nb(), win_data.rng.nb(), win_data.rng.nb()),
+        Action::Rebind => match win_data.actions.last() {
+            None => println!("Nothing to rebind in win {:?}", &window_id),
+            Some(last) => {
+                let now: Vec<_> = win_data.actions.bindings().bindings_of(last).iter()
+                    .map(|binding| binding.to_string()).collect();
+                println!(
+                    "Press the new binding of {:?} ({}) for win {:?}",
+                    last, now.join(", "), &window_id
+                );
+                win_data.actions.capture(last);
+            }
+        },
+        Action::Close => unreachable!()
+    }
+    if let Some(win_data) = win_datas.get_mut(&window_id) {
+        win_data.update_status();
+        win_data.invalidate();
+    }
+}
+
 fn main() {
     let el = EventLoop::new();
     let mut win_manager = WinManager::new().unwrap();
     let mut win_datas: HashMap<WindowId, WinData> = HashMap::default();
+    let start_time = Instant::now();
+    let mut last_frame = start_time;
+    let mut shared_targets: HashMap<WindowId, SharedTarget> = HashMap::default();
+    let mut dnd = DragDrop::new();
+    let assets = match Assets::from_args() {
+        Ok(assets) => assets,
+        Err(e) => {
+            eprintln!("error with the assets loading: {}", e);
+            exit(1);
+        }
+    };
 
+    win_manager.set_srgb(assets.srgb);
+    println!("Seed: {} (replay with --seed {})", assets.seed, assets.seed);
+    println!("Fonts: {}", assets.fonts.names().join(", "));
+    let mut app_rng = DemoRng::new(assets.seed);
+    let clipboard = Clipboard::system();
+    let mut gamepads = Gamepads::system(VirtualGamepad::new());
+    gamepads.set_deadzone(assets.deadzone);
+    // the gamepads drive the focused window
+    let mut focused: Option<WindowId> = None;
     //*
-    for win_idx in 0..3 {
-        let surface = WinSurface::new(
+    let mut win_ids = Vec::new();
+    let nb_windows = if assets.bench.is_some() { 1 } else { 3 };
+    for win_idx in 0..nb_windows {
+        let win_id = win_manager.create_window(
             &el,
             WindowDim::Windowed(800, 400),
             &format!("Test Lumglut multiWin #{}", win_idx+1),
             WindowOpt::default(),
-            //Some(&mut win_manager)
-            //Rc::clone(win_manager.state())
         ).expect(&format!("Glutin surface creation {}", win_idx));
-        //windows.insert(win_idx, surface);
         //
-        let win_id = win_manager.insert_window(surface).unwrap();
         win_datas.insert(
             win_id,
-            WinData::new(win_manager.get_current(win_id).unwrap(), win_idx == 0)
+            WinData::new(
+                win_manager.get_current(win_id).unwrap(), win_idx, &assets, app_rng.fork(), clipboard.clone()
+            )
         );
+        win_ids.push(win_id);
         //
     }
+    if let Some(frames) = assets.bench {
+        let win_id = win_ids[0];
+        bench::report(&bench::run(win_manager.get_current(win_id).unwrap(), &win_datas[&win_id], frames));
+        return;
+    }
+    // the last window shows a preview of the first one
+    let target = SharedTarget::new(win_manager.get_current(win_ids[0]).unwrap(), [400, 200])
+        .expect("shared target creation");
+    shared_targets.insert(win_ids[0], target);
+    win_datas.get_mut(&win_ids[2]).unwrap().preview_of = Some(win_ids[0]);
     //*/
     //
     /*
@@ -138,48 +1069,141 @@ fn main() {
         //*ctrl_flow = ControlFlow::Wait;
         match evt {
             Event::LoopDestroyed => return,
-            Event::WindowEvent {event, window_id} => match event {
-                WindowEvent::Resized(phys_size) => {
-                    let surface = win_manager.get_current(window_id).unwrap();
-                    match surface.ctx() {
-                        CtxCurrWrapper::PossiblyCurrent(ctx) => ctx.resize(phys_size),
-                        CtxCurrWrapper::NotCurrent(_) => panic!("Error with opengl")
-                    }
+            Event::WindowEvent {event, window_id} => {
+                match event {
+                    WindowEvent::Focused(true) => focused = Some(window_id),
+                    WindowEvent::Focused(false) if focused == Some(window_id) => focused = None,
+                    _ => ()
                 }
-                WindowEvent::CloseRequested
-                | WindowEvent::KeyboardInput {
-                    input: KeyboardInput {
-                        state: ElementState::Released,
-                        virtual_keycode: Some(VirtualKeyCode::Escape),
-                        ..
-                    },
-                    ..
-                } => win_manager.remove_window(window_id),
-                WindowEvent::KeyboardInput {
-                    input: KeyboardInput {
-                        state: ElementState::Released,
-                        virtual_keycode: Some(VirtualKeyCode::Space),
+                // the pointer state follows every event, the drags included
+                if let WindowEvent::CursorMoved { position, .. } = &event {
+                    route_cursor(&win_manager, &mut win_datas, window_id, [position.x, position.y]);
+                }
+                else if let Some(win_data) = win_datas.get_mut(&window_id) { win_data.input.handle_event(&event); }
+                // the drags follow the cursor before anything handles it, and
+                // keep the cursor and button events while they run
+                let dragging = dnd.is_dragging();
+                let (dnd_events, consumed) = match event {
+                    WindowEvent::HoveredFile(_) | WindowEvent::HoveredFileCancelled | WindowEvent::DroppedFile(_) =>
+                        (dnd.os_event(window_id, &event).map(|e| vec![(window_id, e)]).unwrap_or_default(), true),
+                    WindowEvent::CursorMoved { .. } | WindowEvent::MouseInput { .. } => {
+                        let events = dnd.handle_event(window_id, &event, &win_manager.screen_rects());
+                        let consumed = dragging || !events.is_empty();
+                        (events, consumed)
+                    }
+                    // Escape or a focus loss drops the drag
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
                         ..
-                    },
-                    ..
-                } => {
-                    let win_data = win_datas.get_mut(&window_id).unwrap();
-                    win_data.next_mode();
-                    win_data.toggle();
-                    println!("Switch mode for win {:?}", &window_id);
+                    } if dragging => (dnd.cancel(), true),
+                    WindowEvent::Focused(false) => (dnd.cancel(), false),
+                    _ => (Vec::new(), false)
+                };
+                dispatch_dnd(&mut win_manager, &mut win_datas, dnd_events);
+                if consumed { return; }
+                // the actions follow every event, and are dropped with the
+                // events taken by the UIs or the camera
+                let mut action = None;
+                if let Some(win_data) = win_datas.get_mut(&window_id) {
+                    action = win_data.actions.handle_event(&event);
+                    // a focused text field has the keyboard, the keys are typed
+                    if let WindowEvent::KeyboardInput { .. } = event {
+                        if win_data.show_ui && win_data.ui.wants_keyboard() { action = None; }
+                    }
+                    if win_data.show_ui && win_data.ui.handle_event(&event) {
+                        win_data.invalidate();
+                        return;
+                    }
+                    #[cfg(feature = "nuklear")]
+                    {
+                        if win_data.show_ui && win_data.nk.handle_event(&event) {
+                            win_data.invalidate();
+                            return;
+                        }
+                    }
+                    if win_data.tree.handle_event(&event) {
+                        win_data.apply_clicks();
+                        return;
+                    }
+                    if win_data.is_3d() && win_data.camera.handle_event(&event) {
+                        win_data.update_status();
+                        win_data.invalidate();
+                        return;
+                    }
                 }
-                WindowEvent::KeyboardInput {
-                    input: KeyboardInput {state: ElementState::Released, ..},
-                    ..
-                } => {
-                    let win_data = win_datas.get_mut(&window_id).unwrap();
-                    win_data.bgcol = [new_nb(), new_nb(), new_nb(), 1.0];
-                    win_data.toggle();
+                match event {
+                    WindowEvent::Resized(phys_size) => {
+                        let surface = win_manager.get_current(window_id).unwrap();
+                        match surface.ctx() {
+                            CtxCurrWrapper::PossiblyCurrent(ctx) => ctx.resize(phys_size),
+                            CtxCurrWrapper::NotCurrent(_) => panic!("Error with opengl")
+                        }
+                        if let Some(win_data) = win_datas.get_mut(&window_id) {
+                            win_data.camera.set_aspect(phys_size.width, phys_size.height);
+                            if let Err(e) = win_data.post.resize(surface, [phys_size.width, phys_size.height]) {
+                                eprintln!("post chain resize error, post effects off: {:?}", e);
+                                win_data.post.set_effects(Vec::new());
+                                win_data.post_preset = 0;
+                                win_data.update_status();
+                            }
+                            win_data.tree.resize([phys_size.width, phys_size.height]);
+                        }
+                    }
+                    WindowEvent::CloseRequested => action = Some(Action::Close),
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        if let Some(win_data) = win_datas.get_mut(&window_id) {
+                            win_data.scroll(input::scroll_lines(&delta)[1] as f32);
+                        }
+                    }
+                    WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+                        if let Some(win_data) = win_datas.get(&window_id) {
+                            dnd.press(window_id, win_data.drag_payload(), &win_manager.screen_rects());
+                        }
+                    }
+                    _ => ()
+                }
+                if let Some(action) = action {
+                    run_action(&mut win_manager, &mut win_datas, &mut shared_targets, &mut dnd, window_id, action);
                 }
-                _ => ()
             }
             Event::MainEventsCleared => {
+                let now = Instant::now();
+                let dt = now.duration_since(last_frame).as_secs_f32();
+                last_frame = now;
+                for event in gamepads.poll() {
+                    match &event {
+                        PadEvent::Connected { id, name } => println!("Gamepad {} connected: {}", id, name),
+                        PadEvent::Disconnected { id } => println!("Gamepad {} disconnected", id),
+                        _ => ()
+                    }
+                    let window_id = match focused { Some(window_id) => window_id, None => continue };
+                    let action = win_datas.get_mut(&window_id).and_then(|win_data| win_data.actions.handle_pad(&event));
+                    if let Some(action) = action {
+                        run_action(&mut win_manager, &mut win_datas, &mut shared_targets, &mut dnd, window_id, action);
+                    }
+                }
+                if let Some(win_data) = focused.and_then(|window_id| win_datas.get_mut(&window_id)) {
+                    let (x, y) = (gamepads.stick(PadAxis::RightX), gamepads.stick(PadAxis::RightY));
+                    if win_data.is_3d() && x.abs() + y.abs() > 0.0 {
+                        win_data.camera.turn(x, y, dt);
+                        win_data.invalidate();
+                    }
+                }
                 for (win_id, win_data) in &mut win_datas {
+                    if win_data.is_3d() && win_data.camera.is_moving() {
+                        win_data.camera.update(dt);
+                        win_data.invalidate();
+                    }
+                    // the status bar shows the pointer, its text only redraws the tree when it changes
+                    if win_data.input.changed() {
+                        win_data.update_status();
+                        win_data.input.end_frame();
+                    }
+                    if win_data.is_animated() { win_data.invalidate(); }
                     if win_data.need_redraw() {
                         match win_manager.get_current(win_id.clone()).unwrap().ctx() {
                             CtxCurrWrapper::PossiblyCurrent(ctx) => ctx.window().request_redraw(),
@@ -192,13 +1216,10 @@ fn main() {
             Event::RedrawRequested(win_id) => {
                 let surface = win_manager.get_current(win_id.clone()).unwrap();
                 let back_buffer = surface.back_buffer().unwrap();
-                let win_data = win_datas.get(&win_id).unwrap();
-                //
-                let _tess = TessBuilder::new(surface)
-                    .add_vertices(TRIS_FULL.tri_verts.clone())
-                    .set_mode(Mode::Triangle)
-                    .build()
-                    .unwrap();
+                let win_data = win_datas.get_mut(&win_id).unwrap();
+                win_data.stream(start_time.elapsed().as_secs_f32());
+                win_data.prepare_batch(surface);
+                let win_data = &*win_data;
                 //
                 /*
                 if let CtxCurrWrapper::PossiblyCurrent(ctx) = surface.ctx() {
@@ -209,34 +1230,38 @@ fn main() {
                 */
                 //
                 //
-                surface.pipeline_builder().pipeline(
-                //win_manager.pipeline_builder().pipeline(
-                //
-                    &back_buffer,
-                    &PipelineState::default().set_clear_color(win_data.bgcol),
-                    |_, mut shd_gate| {
-                        shd_gate.shade(&win_data.program, |_, mut rdr_gate| {
-                            //
-                            rdr_gate.render(&RenderState::default(), |mut tess_gate| {
-                                let tess = match win_data.get_mode() {
-                                    TessMethod::Direct => &win_data.tesses[0],
-                                    TessMethod::Indexed => &win_data.tesses[1],
-                                    TessMethod::DirectDeinter => &win_data.tesses[2],
-                                    TessMethod::IndexedDeinter => &win_data.tesses[3]
-                                };
-                                //
-                                //
-                                tess_gate.render(tess);
-                            });
-                            //
-                        });
+                let produced = shared_targets.get(&win_id);
+                {
+                    let builder = surface.pipeline_builder();
+                    if !win_data.post.is_active() { win_data.render(&builder, &back_buffer); }
+                    else {
+                        win_data.render(&builder, win_data.post.scene());
+                        win_data.post.apply(&builder, &back_buffer);
                     }
-                );
+                    if let Some(target) = produced { win_data.render_scene(&builder, target.fb()); }
+                    if let Some(shared) = win_data.preview().and_then(|pid| shared_targets.get(&pid)) {
+                        win_data.render_preview(&builder, &back_buffer, shared);
+                    }
+                }
+                let win_data = win_datas.get_mut(&win_id).unwrap();
+                win_data.copy_frame(surface.size());
                 surface.swap_buffers();
+                if let Some(target) = produced {
+                    target.signal();
+                    for consumer in win_datas.values_mut() {
+                        if consumer.preview() == Some(win_id) { consumer.invalidate(); }
+                    }
+                }
             }
             _ => ()
         }
         if win_manager.len() == 0 { *ctrl_flow = ControlFlow::Exit }
+        else if win_datas.values().any(|wd| wd.need_redraw() || wd.is_animated() || (wd.is_3d() && wd.camera.is_moving())) {
+            *ctrl_flow = ControlFlow::Poll
+        }
+        else if let Some(period) = gamepads.poll_period() {
+            *ctrl_flow = ControlFlow::WaitUntil(Instant::now() + period)
+        }
         else { *ctrl_flow = ControlFlow::Wait }
     });
 }
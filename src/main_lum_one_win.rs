@@ -6,7 +6,7 @@ use luminance_glutin::{
 use std::process::exit;
 
 mod in_utils;
-use in_utils::new_nb;
+use in_utils::DemoRng;
 
 /*
 fn main() {
@@ -35,6 +35,7 @@ fn main() {
 fn main_loop(mut surface: GlutinSurface) {
     let back_buff = surface.back_buffer().unwrap();
     let mut color = [0.0, 0.5, 1.0, 1.0];
+    let mut rng = DemoRng::new(DemoRng::random_seed());
     'app: loop {
         for evt in surface.poll_events() {
             if let Event::WindowEvent {event, ..} = evt {
@@ -43,7 +44,7 @@ fn main_loop(mut surface: GlutinSurface) {
                     WindowEvent::KeyboardInput {
                         input: KeyboardInput {state: ElementState::Released, ..},
                         ..
-                    } => { color = [new_nb(), new_nb(), new_nb(), 1.0]; }
+                    } => { color = [rng.nb(), rng.nb(), rng.nb(), 1.0]; }
                     _ => ()
                 }
             }
@@ -0,0 +1,196 @@
+use gl;
+use luminance::blending::{Equation, Factor};
+use luminance::depth_test::DepthComparison;
+use luminance::face_culling::{FaceCulling, FaceCullingMode, FaceCullingOrder};
+use luminance::render_state::RenderState;
+
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum BlendMode { Opaque, Alpha, Premultiplied, Additive }
+
+impl BlendMode {
+    fn blending(&self) -> Option<(Equation, Factor, Factor)> {
+        match self {
+            BlendMode::Opaque => None,
+            BlendMode::Alpha => Some((Equation::Additive, Factor::SrcAlpha, Factor::SrcAlphaComplement)),
+            BlendMode::Premultiplied => Some((Equation::Additive, Factor::One, Factor::SrcAlphaComplement)),
+            BlendMode::Additive => Some((Equation::Additive, Factor::SrcAlpha, Factor::One))
+        }
+    }
+
+    pub fn is_transparent(&self) -> bool { *self != BlendMode::Opaque }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Cull { Front, Back }
+
+/// Clip rectangle in framebuffer pixels, origin at the bottom left like gl.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Scissor {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+impl Scissor {
+    /// Rectangle given with a top left origin, as the window coordinates,
+    /// in a framebuffer of height `fb_height`.
+    pub fn from_top_left(pos: [u32; 2], size: [u32; 2], fb_height: u32) -> Self {
+        Scissor {
+            x: pos[0],
+            y: fb_height.saturating_sub(pos[1] + size[1]),
+            width: size[0],
+            height: size[1]
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MaterialError {
+    /// gl doesn't write the depth when the test is disabled.
+    DepthWriteWithoutTest,
+    /// A transparent surface writing the depth hides what is drawn behind it
+    /// afterwards.
+    TransparentDepthWrite(BlendMode),
+    EmptyScissor(Scissor),
+    ScissorOutOfBounds { scissor: Scissor, fb_size: [u32; 2] }
+}
+
+/// Render state of a draw: blending, culling, depth and clipping.
+///
+/// Build it with the chained setters ending with `checked`, the scissor
+/// being checked against the target size with `fits`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Material {
+    pub blend: BlendMode,
+    pub cull: Option<Cull>,
+    pub depth_test: Option<DepthComparison>,
+    pub depth_write: bool,
+    pub scissor: Option<Scissor>
+}
+
+impl Default for Material {
+    /// Flat drawing: opaque, no culling, no depth.
+    fn default() -> Self {
+        Material { blend: BlendMode::Opaque, cull: None, depth_test: None, depth_write: false, scissor: None }
+    }
+}
+
+impl Material {
+    /// Opaque 3D meshes, back faces culled.
+    pub fn solid() -> Self {
+        Material {
+            cull: Some(Cull::Back),
+            depth_test: Some(DepthComparison::Less),
+            depth_write: true,
+            ..Material::default()
+        }
+    }
+
+    /// Transparent 2D overlay, for the sprites and the UI.
+    pub fn overlay(blend: BlendMode) -> Self {
+        Material { blend, ..Material::default() }
+    }
+
+    pub fn blend(mut self, blend: BlendMode) -> Self { self.blend = blend; self }
+
+    pub fn cull(mut self, cull: Option<Cull>) -> Self { self.cull = cull; self }
+
+    pub fn depth(mut self, test: Option<DepthComparison>, write: bool) -> Self {
+        self.depth_test = test;
+        self.depth_write = write;
+        self
+    }
+
+    pub fn scissor(mut self, scissor: Option<Scissor>) -> Self { self.scissor = scissor; self }
+
+    /// The material if its states go together.
+    pub fn checked(self) -> Result<Self, MaterialError> {
+        self.validate()?;
+        Ok(self)
+    }
+
+    pub fn validate(&self) -> Result<(), MaterialError> {
+        if self.depth_write && self.depth_test.is_none() {
+            return Err(MaterialError::DepthWriteWithoutTest);
+        }
+        if self.depth_write && self.blend.is_transparent() {
+            return Err(MaterialError::TransparentDepthWrite(self.blend));
+        }
+        match self.scissor {
+            Some(scissor) if scissor.width == 0 || scissor.height == 0 => Err(MaterialError::EmptyScissor(scissor)),
+            _ => Ok(())
+        }
+    }
+
+    /// Check the scissor is in a target of `fb_size`.
+    pub fn fits(&self, fb_size: [u32; 2]) -> Result<(), MaterialError> {
+        match self.scissor {
+            Some(scissor) if scissor.x.saturating_add(scissor.width) > fb_size[0]
+                || scissor.y.saturating_add(scissor.height) > fb_size[1] =>
+                Err(MaterialError::ScissorOutOfBounds { scissor, fb_size }),
+            _ => Ok(())
+        }
+    }
+
+    pub fn render_state(&self) -> RenderState {
+        let culling = self.cull.map(|cull| FaceCulling::new(
+            FaceCullingOrder::CCW,
+            match cull { Cull::Front => FaceCullingMode::Front, Cull::Back => FaceCullingMode::Back }
+        ));
+        RenderState::default()
+            .set_blending(self.blend.blending())
+            .set_face_culling(culling)
+            .set_depth_test(self.depth_test)
+    }
+
+    /// Run the draw `f` with the state luminance doesn't track, the depth
+    /// mask and the scissor, restored to their defaults afterwards.
+    pub fn scoped<F>(&self, f: F)
+    where F: FnOnce(RenderState) {
+        unsafe {
+            gl::DepthMask(if self.depth_write { gl::TRUE } else { gl::FALSE });
+            if let Some(Scissor { x, y, width, height }) = self.scissor {
+                gl::Enable(gl::SCISSOR_TEST);
+                gl::Scissor(x as i32, y as i32, width as i32, height as i32);
+            }
+        }
+        f(self.render_state());
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            if self.scissor.is_some() { gl::Disable(gl::SCISSOR_TEST); }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_states() {
+        assert_eq!(Material::solid().checked(), Ok(Material::solid()));
+        assert_eq!(Material::default().depth(None, true).checked(), Err(MaterialError::DepthWriteWithoutTest));
+        assert_eq!(
+            Material::solid().blend(BlendMode::Alpha).checked(),
+            Err(MaterialError::TransparentDepthWrite(BlendMode::Alpha))
+        );
+        // the test alone is fine for the transparent surfaces
+        assert!(Material::solid().depth(Some(DepthComparison::Less), false).blend(BlendMode::Additive).checked().is_ok());
+    }
+
+    #[test]
+    fn scissors() {
+        let clip = |x, y, width, height| Material::overlay(BlendMode::Alpha).scissor(Some(Scissor { x, y, width, height }));
+        assert_eq!(clip(0, 0, 0, 10).checked(), Err(MaterialError::EmptyScissor(Scissor { x: 0, y: 0, width: 0, height: 10 })));
+        assert!(clip(10, 10, 1, 0).checked().is_err());
+        assert_eq!(clip(0, 0, 100, 50).fits([100, 50]), Ok(()));
+        let out = Scissor { x: 50, y: 0, width: 51, height: 50 };
+        assert_eq!(
+            clip(50, 0, 51, 50).fits([100, 50]),
+            Err(MaterialError::ScissorOutOfBounds { scissor: out, fb_size: [100, 50] })
+        );
+        assert!(clip(0, u32::MAX, 10, 10).fits([100, 50]).is_err());
+        assert_eq!(Scissor::from_top_left([10, 5], [20, 10], 50), Scissor { x: 10, y: 35, width: 20, height: 10 });
+    }
+}
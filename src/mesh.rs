@@ -0,0 +1,314 @@
+use gltf::mesh::Mode as GltfMode;
+use luminance::context::GraphicsContext;
+use luminance::tess::{Mode, Tess, TessBuilder, TessError};
+use luminance_derive::Vertex;
+use std::fmt;
+use std::path::Path;
+
+use crate::color::Color;
+use crate::in_utils::{
+    Semantics, TessMethod,
+    VertexColor, VertexNormal, VertexPosition3, VertexUV
+};
+
+#[derive(Debug)]
+pub enum MeshError {
+    ObjError(tobj::LoadError),
+    GltfError(gltf::Error),
+    TessError(TessError),
+    MeshInternError(String)
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            MeshError::ObjError(ref e) => write!(f, "OBJ loading error: {:?}", e),
+            MeshError::GltfError(ref e) => write!(f, "glTF loading error: {}", e),
+            MeshError::TessError(ref e) => write!(f, "Mesh tess creation error: {:?}", e),
+            MeshError::MeshInternError(ref e) => write!(f, "Mesh intern error: {}", e)
+        }
+    }
+}
+
+impl From<tobj::LoadError> for MeshError {
+    fn from(e: tobj::LoadError) -> Self { MeshError::ObjError(e) }
+}
+
+impl From<gltf::Error> for MeshError {
+    fn from(e: gltf::Error) -> Self { MeshError::GltfError(e) }
+}
+
+impl From<TessError> for MeshError {
+    fn from(e: TessError) -> Self { MeshError::TessError(e) }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[vertex(sem = "Semantics")]
+pub struct MeshVertex {
+    pos: VertexPosition3,
+    nor: VertexNormal,
+    uv: VertexUV,
+    rgb: VertexColor
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[vertex(sem = "Semantics")]
+pub struct MeshPos { pos: VertexPosition3 }
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[vertex(sem = "Semantics")]
+pub struct MeshNor { nor: VertexNormal }
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[vertex(sem = "Semantics")]
+pub struct MeshUV { uv: VertexUV }
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[vertex(sem = "Semantics")]
+pub struct MeshCol {
+    rgb: VertexColor
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub colors: Vec<[f32; 3]>,
+    pub indices: Option<Vec<u32>>
+}
+
+impl MeshData {
+    pub fn cube() -> MeshData {
+        let positions = vec![
+            [-0.5, -0.5, -0.5], [0.5, -0.5, -0.5], [0.5, 0.5, -0.5], [-0.5, 0.5, -0.5],
+            [-0.5, -0.5, 0.5], [0.5, -0.5, 0.5], [0.5, 0.5, 0.5], [-0.5, 0.5, 0.5]
+        ];
+        let colors = vec![
+            [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0]
+        ];
+        let indices = vec![
+            0, 2, 1, 0, 3, 2, // back
+            4, 5, 6, 4, 6, 7, // front
+            0, 4, 7, 0, 7, 3, // left
+            1, 2, 6, 1, 6, 5, // right
+            3, 7, 6, 3, 6, 2, // top
+            0, 1, 5, 0, 5, 4  // bottom
+        ];
+        MeshData { positions, colors, indices: Some(indices), ..MeshData::default() }.complete()
+    }
+
+    /// Load a mesh file, the format is picked from the extension (obj, gltf
+    /// or glb). All the meshes of the file are merged into one.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<MeshData, MeshError> {
+        let path = path.as_ref();
+        let ext = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+        let meshes = match ext.as_str() {
+            "obj" => load_obj(path)?,
+            "gltf" | "glb" => load_gltf(path)?,
+            _ => return Err(MeshError::MeshInternError(format!("unknown mesh format: {:?}", path)))
+        };
+        Ok(meshes.into_iter().fold(MeshData::default(), |acc, m| acc.merge(m)))
+    }
+
+    pub fn len(&self) -> usize { self.positions.len() }
+
+    pub fn is_empty(&self) -> bool { self.positions.is_empty() }
+
+    pub fn merge(mut self, other: MeshData) -> MeshData {
+        let offset = self.len() as u32;
+        let self_inds = self.indices.take().unwrap_or_else(|| (0..offset).collect());
+        let other_inds = other.indices.unwrap_or_else(|| (0..other.positions.len() as u32).collect());
+        self.positions.extend(other.positions);
+        self.normals.extend(other.normals);
+        self.uvs.extend(other.uvs);
+        self.colors.extend(other.colors);
+        self.indices = Some(self_inds.into_iter().chain(other_inds.into_iter().map(|i| i + offset)).collect());
+        self
+    }
+
+    /// Fill the missing attributes so every one has as many entries as positions.
+    pub fn complete(mut self) -> MeshData {
+        let nb = self.len();
+        self.normals.resize(nb, [0.0, 0.0, 0.0]);
+        self.uvs.resize(nb, [0.0, 0.0]);
+        self.colors.resize(nb, [1.0, 1.0, 1.0]);
+        self
+    }
+
+    /// Check the data of a file before `complete`: every attribute is empty
+    /// or has an entry per position, and the indices are in range.
+    fn checked(self) -> Result<MeshData, MeshError> {
+        let nb = self.len();
+        let attrs = [("normals", self.normals.len()), ("uvs", self.uvs.len()), ("colors", self.colors.len())];
+        for &(name, len) in &attrs {
+            if len != 0 && len != nb {
+                return Err(MeshError::MeshInternError(format!("{} {} for {} positions", len, name, nb)));
+            }
+        }
+        if let Some(i) = self.indices.iter().flatten().find(|i| **i as usize >= nb) {
+            return Err(MeshError::MeshInternError(format!("index {} out of {} positions", i, nb)));
+        }
+        Ok(self)
+    }
+
+    fn unindexed(&self) -> MeshData {
+        match &self.indices {
+            None => self.clone(),
+            Some(inds) => {
+                let pick = |i: &u32| *i as usize;
+                MeshData {
+                    positions: inds.iter().map(|i| self.positions[pick(i)]).collect(),
+                    normals: inds.iter().map(|i| self.normals[pick(i)]).collect(),
+                    uvs: inds.iter().map(|i| self.uvs[pick(i)]).collect(),
+                    colors: inds.iter().map(|i| self.colors[pick(i)]).collect(),
+                    indices: None
+                }
+            }
+        }
+    }
+
+    fn interleaved(&self) -> Vec<MeshVertex> {
+        (0..self.len()).map(|i| MeshVertex {
+            pos: VertexPosition3::new(self.positions[i]),
+            nor: VertexNormal::new(self.normals[i]),
+            uv: VertexUV::new(self.uvs[i]),
+            rgb: VertexColor::new(self.colors[i])
+        }).collect()
+    }
+
+    pub fn to_tess<C>(&self, ctx: &mut C, method: TessMethod) -> Result<Tess, MeshError>
+    where C: GraphicsContext {
+        let indexed = match method {
+            TessMethod::Indexed | TessMethod::IndexedDeinter => self.indices.is_some(),
+            TessMethod::Direct | TessMethod::DirectDeinter => false
+        };
+        let data = if indexed { self.clone() } else { self.unindexed() };
+        let builder = match method {
+            TessMethod::Direct | TessMethod::Indexed =>
+                TessBuilder::new(ctx).add_vertices(data.interleaved()),
+            TessMethod::DirectDeinter | TessMethod::IndexedDeinter => {
+                let poss: Vec<_> = data.positions.iter().map(|p| MeshPos {pos: VertexPosition3::new(*p)}).collect();
+                let nors: Vec<_> = data.normals.iter().map(|n| MeshNor {nor: VertexNormal::new(*n)}).collect();
+                let uvs: Vec<_> = data.uvs.iter().map(|t| MeshUV {uv: VertexUV::new(*t)}).collect();
+                let cols: Vec<_> = data.colors.iter().map(|c| MeshCol {rgb: VertexColor::new(*c)}).collect();
+                TessBuilder::new(ctx)
+                    .add_vertices(poss)
+                    .add_vertices(nors)
+                    .add_vertices(uvs)
+                    .add_vertices(cols)
+            }
+        };
+        let builder = match &data.indices {
+            Some(inds) if indexed => builder.set_indices(inds.as_slice()),
+            _ => builder
+        };
+        Ok(builder.set_mode(Mode::Triangle).build()?)
+    }
+
+    /// The colours for the framebuffer, see `Color::vertex_rgb`.
+    pub fn for_fb(&self, srgb_fb: bool) -> MeshData {
+        MeshData {
+            colors: self.colors.iter().map(|rgb| Color::fb_rgb(*rgb, srgb_fb)).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Build the four tesses matching the `TessMethod` variants, in order.
+    pub fn to_tesses<C>(&self, ctx: &mut C) -> Result<[Tess; 4], MeshError>
+    where C: GraphicsContext {
+        Ok([
+            self.to_tess(ctx, TessMethod::Direct)?,
+            self.to_tess(ctx, TessMethod::Indexed)?,
+            self.to_tess(ctx, TessMethod::DirectDeinter)?,
+            self.to_tess(ctx, TessMethod::IndexedDeinter)?
+        ])
+    }
+}
+
+fn chunk3(v: &[f32]) -> Vec<[f32; 3]> {
+    v.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect()
+}
+
+pub fn load_obj(path: &Path) -> Result<Vec<MeshData>, MeshError> {
+    let (models, materials) = tobj::load_obj(path)?;
+    models.into_iter().map(|model| {
+        let mesh = model.mesh;
+        let color = mesh.material_id
+            .and_then(|id| materials.get(id))
+            .map(|mat| mat.diffuse)
+            .unwrap_or([1.0, 1.0, 1.0]);
+        let positions = chunk3(&mesh.positions);
+        MeshData {
+            colors: vec![color; positions.len()],
+            positions,
+            normals: chunk3(&mesh.normals),
+            uvs: mesh.texcoords.chunks_exact(2).map(|c| [c[0], c[1]]).collect(),
+            indices: Some(mesh.indices)
+        }.checked().map(MeshData::complete)
+    }).collect()
+}
+
+pub fn load_gltf(path: &Path) -> Result<Vec<MeshData>, MeshError> {
+    let (document, buffers, _) = gltf::import(path)?;
+    let mut meshes = Vec::new();
+    for mesh in document.meshes() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != GltfMode::Triangles { continue; }
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                Some(iter) => iter.collect(),
+                None => continue
+            };
+            meshes.push(MeshData {
+                positions,
+                normals: reader.read_normals().map(|iter| iter.collect()).unwrap_or_default(),
+                uvs: reader.read_tex_coords(0)
+                    .map(|tc| tc.into_f32().collect())
+                    .unwrap_or_default(),
+                colors: reader.read_colors(0)
+                    .map(|cols| cols.into_rgb_f32().collect())
+                    .unwrap_or_default(),
+                indices: reader.read_indices().map(|inds| inds.into_u32().collect())
+            }.checked()?.complete());
+        }
+    }
+    if meshes.is_empty() {
+        Err(MeshError::MeshInternError(format!("no triangle mesh in {:?}", path)))
+    }
+    else { Ok(meshes) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> MeshData {
+        MeshData {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            indices: Some(vec![0, 1, 2]),
+            ..MeshData::default()
+        }
+    }
+
+    #[test]
+    fn checked_rejects_bad_files() {
+        let mesh = triangle().checked().unwrap().complete();
+        assert_eq!(mesh.unindexed().len(), 3);
+        let out_of_range = MeshData { indices: Some(vec![0, 1, 3]), ..triangle() };
+        assert!(out_of_range.checked().is_err());
+        let short_normals = MeshData { normals: vec![[0.0, 0.0, 1.0]; 2], ..triangle() };
+        assert!(short_normals.checked().is_err());
+        let long_uvs = MeshData { uvs: vec![[0.0, 0.0]; 4], ..triangle() };
+        assert!(long_uvs.checked().is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use glutin::event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use luminance::context::GraphicsContext;
+use nuklear::{
+    Allocator, AntiAliasing, Buffer, Button, Context, ConvertConfig, DrawNullTexture,
+    DrawVertexLayoutAttribute, DrawVertexLayoutElements, DrawVertexLayoutFormat, FontAtlas,
+    FontAtlasFormat, FontConfig, Handle, Key, Vec2
+};
+use std::mem;
+
+use crate::batch::{BatchKey, BatchRenderer, TexId};
+use crate::color::Color;
+use crate::in_utils::BatchVertex;
+use crate::material::{BlendMode, Scissor};
+use crate::text::BERYLIUM;
+use crate::texture::{TexError, TexFilter, TexImage, TexOpt};
+
+/// First batch layer of the Nuklear output, over the native GUI.
+pub const NK_LAYER: u16 = 64;
+
+const VBUF_SIZE: usize = 512 * 1024;
+const EBUF_SIZE: usize = 128 * 1024;
+
+/// Vertex written by `nk_convert`, see `vertex_layout`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+struct NkVertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+    col: [u8; 4]
+}
+
+fn vertex_layout() -> DrawVertexLayoutElements {
+    DrawVertexLayoutElements::new(&[
+        (DrawVertexLayoutAttribute::Position, DrawVertexLayoutFormat::Float, 0),
+        (DrawVertexLayoutAttribute::TexCoord, DrawVertexLayoutFormat::Float, 8),
+        (DrawVertexLayoutAttribute::Color, DrawVertexLayoutFormat::R8G8B8A8, 16),
+        (DrawVertexLayoutAttribute::AttributeCount, DrawVertexLayoutFormat::Count, 0)
+    ])
+}
+
+/// Input received between two frames, replayed between `input_begin` and
+/// `input_end` since Nuklear wants it all at once.
+enum NkInput {
+    Motion(i32, i32),
+    Button(Button, i32, i32, bool),
+    Unicode(char),
+    Key(Key, bool),
+    Scroll(f32)
+}
+
+fn nk_key(key: VirtualKeyCode) -> Option<Key> {
+    match key {
+        VirtualKeyCode::Back => Some(Key::Backspace),
+        VirtualKeyCode::Delete => Some(Key::Del),
+        VirtualKeyCode::Return => Some(Key::Enter),
+        VirtualKeyCode::Tab => Some(Key::Tab),
+        VirtualKeyCode::Left => Some(Key::Left),
+        VirtualKeyCode::Right => Some(Key::Right),
+        VirtualKeyCode::Up => Some(Key::Up),
+        VirtualKeyCode::Down => Some(Key::Down),
+        VirtualKeyCode::Home => Some(Key::LineStart),
+        VirtualKeyCode::End => Some(Key::LineEnd),
+        _ => None
+    }
+}
+
+/// Nuklear context of a window, its vertex output going through the window
+/// `BatchRenderer` with a scissor per draw command. The Nuklear UIs written
+/// for other backends run as they are in the `frame` closure.
+pub struct NkBackend {
+    // the context keeps pointers to the allocator and the font
+    ctx: Box<Context>,
+    _atlas: FontAtlas,
+    _allo: Box<Allocator>,
+    cmds: Buffer,
+    config: ConvertConfig,
+    _layout: Box<DrawVertexLayoutElements>,
+    // typed for the alignment, handed to Nuklear as bytes
+    vbuf: Vec<NkVertex>,
+    ebuf: Vec<u16>,
+    tex: TexId,
+    srgb: bool,
+    size: [u32; 2],
+    cursor: [i32; 2],
+    pending: Vec<NkInput>
+}
+
+impl NkBackend {
+    /// Bake the Berylium font at `font_px` in a texture of `batch`, the ctx
+    /// must be current.
+    pub fn new<C>(ctx: &mut C, batch: &mut BatchRenderer, font_px: f32, srgb: bool) -> Result<Self, TexError>
+    where C: GraphicsContext {
+        let mut allo = Box::new(Allocator::new_vec());
+        let mut atlas = FontAtlas::new(&mut allo);
+        let mut font_cfg = FontConfig::with_size(font_px);
+        font_cfg.set_oversample_h(3);
+        font_cfg.set_oversample_v(2);
+        font_cfg.set_ttf(BERYLIUM);
+        font_cfg.set_ttf_data_owned_by_atlas(false);
+        let font = atlas.add_font_with_config(&font_cfg)
+            .ok_or_else(|| TexError::TexInternError("nuklear font baking".to_string()))?;
+        let image = {
+            let (texels, w, h) = atlas.bake(FontAtlasFormat::Rgba32);
+            TexImage { size: [w, h], texels: texels.to_vec() }
+        };
+        let opt = TexOpt { filter: TexFilter::Linear, ..TexOpt::default() };
+        let tex = batch.add_texture(image.upload(ctx, &opt, false)?);
+        // the null texture is the white texel nuklear puts in its atlas, so a
+        // window is drawn with a single texture
+        let mut null = DrawNullTexture::default();
+        atlas.end(Handle::from_id(tex as i32), Some(&mut null));
+        let nk_ctx = Box::new(Context::new(&mut allo, atlas.font(font).unwrap().handle()));
+        let layout = Box::new(vertex_layout());
+        let mut config = ConvertConfig::default();
+        config.set_null(null);
+        config.set_vertex_layout(&layout);
+        config.set_vertex_size(mem::size_of::<NkVertex>());
+        // the batch vertices have no alpha, the AA fringes would be opaque
+        config.set_shape_aa(AntiAliasing::Off);
+        config.set_line_aa(AntiAliasing::Off);
+        config.set_circle_segment_count(22);
+        config.set_curve_segment_count(22);
+        config.set_arc_segment_count(22);
+        config.set_global_alpha(1.0);
+        Ok(NkBackend {
+            ctx: nk_ctx,
+            cmds: Buffer::new(&mut allo),
+            _atlas: atlas,
+            _allo: allo,
+            config,
+            _layout: layout,
+            vbuf: vec![NkVertex::default(); VBUF_SIZE / mem::size_of::<NkVertex>()],
+            ebuf: vec![0; EBUF_SIZE / mem::size_of::<u16>()],
+            tex,
+            srgb,
+            size: [1, 1],
+            cursor: [0, 0],
+            pending: Vec::new()
+        })
+    }
+
+    /// True when the last frame had the mouse over a Nuklear window or an
+    /// item in use, the events then belong to it.
+    pub fn wants_input(&self) -> bool {
+        self.ctx.window_is_any_hovered() || self.ctx.item_is_any_active()
+    }
+
+    /// Queue a window event for the next frame, returns true if Nuklear
+    /// takes it, like the camera `handle_event`.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        let input = match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = [position.x as i32, position.y as i32];
+                // the motion is always given, Nuklear has to see the cursor leave
+                self.pending.push(NkInput::Motion(self.cursor[0], self.cursor[1]));
+                return self.wants_input();
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => Button::Left,
+                    MouseButton::Middle => Button::Middle,
+                    MouseButton::Right => Button::Right,
+                    MouseButton::Other(_) => return false
+                };
+                NkInput::Button(button, self.cursor[0], self.cursor[1], *state == ElementState::Pressed)
+            }
+            WindowEvent::MouseWheel { delta, .. } => NkInput::Scroll(match delta {
+                MouseScrollDelta::LineDelta(_, y) => *y,
+                MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 20.0
+            }),
+            WindowEvent::ReceivedCharacter(c) if !c.is_control() => NkInput::Unicode(*c),
+            WindowEvent::KeyboardInput { input, .. } => {
+                match input.virtual_keycode.and_then(nk_key) {
+                    Some(key) => NkInput::Key(key, input.state == ElementState::Pressed),
+                    None => return self.ctx.item_is_any_active()
+                }
+            }
+            _ => return false
+        };
+        if !self.wants_input() { return false; }
+        self.pending.push(input);
+        true
+    }
+
+    /// Give the queued input to Nuklear, then declare the UI of the frame with
+    /// `f`, for a window of `size` pixels.
+    pub fn frame<F>(&mut self, size: [u32; 2], f: F)
+    where F: FnOnce(&mut Context) {
+        self.size = size;
+        self.ctx.input_begin();
+        for input in self.pending.drain(..) {
+            match input {
+                NkInput::Motion(x, y) => self.ctx.input_motion(x, y),
+                NkInput::Button(button, x, y, down) => self.ctx.input_button(button, x, y, down),
+                NkInput::Unicode(c) => self.ctx.input_unicode(c),
+                NkInput::Key(key, down) => self.ctx.input_key(key, down),
+                NkInput::Scroll(dy) => self.ctx.input_scroll(Vec2 { x: 0.0, y: dy })
+            }
+        }
+        self.ctx.input_end();
+        f(&mut self.ctx);
+    }
+
+    /// Convert the frame to triangles and submit them to `batch`, one
+    /// submission per Nuklear draw command.
+    pub fn submit(&mut self, batch: &mut BatchRenderer) {
+        {
+            // a byte view of the typed buffers is always aligned
+            let vbytes = unsafe {
+                std::slice::from_raw_parts_mut(
+                    self.vbuf.as_mut_ptr() as *mut u8, self.vbuf.len() * mem::size_of::<NkVertex>()
+                )
+            };
+            let ebytes = unsafe {
+                std::slice::from_raw_parts_mut(self.ebuf.as_mut_ptr() as *mut u8, self.ebuf.len() * mem::size_of::<u16>())
+            };
+            let mut vbuf = Buffer::with_fixed(vbytes);
+            let mut ebuf = Buffer::with_fixed(ebytes);
+            self.ctx.convert(&mut self.cmds, &mut vbuf, &mut ebuf, &self.config);
+        }
+        // only what this conversion wrote: the elements of its commands and
+        // the vertices they index, a full buffer drops the frame
+        let elem_count: usize = self.ctx.draw_command_iterator(&self.cmds).map(|cmd| cmd.elem_count() as usize).sum();
+        let vert_count = self.ebuf[..elem_count.min(self.ebuf.len())].iter()
+            .map(|ind| *ind as usize + 1)
+            .max()
+            .unwrap_or(0);
+        if elem_count >= self.ebuf.len() || vert_count >= self.vbuf.len() {
+            eprintln!("Nuklear frame over the vertex buffers, not drawn");
+            self.ctx.clear();
+            return;
+        }
+        let inds = &self.ebuf[..elem_count];
+        let verts = &self.vbuf[..vert_count];
+        let (w, h) = (self.size[0].max(1) as f32, self.size[1].max(1) as f32);
+        let mut offset = 0;
+        for (i, cmd) in self.ctx.draw_command_iterator(&self.cmds).enumerate() {
+            let count = cmd.elem_count() as usize;
+            let range = offset..offset + count;
+            offset += count;
+            let clip = cmd.clip_rect();
+            let (x0, y0) = (clip.x.max(0.0), clip.y.max(0.0));
+            let (x1, y1) = ((clip.x + clip.w).min(w), (clip.y + clip.h).min(h));
+            if count == 0 || x1 <= x0 || y1 <= y0 { continue; }
+            let key = BatchKey {
+                // the commands are in painter order
+                layer: NK_LAYER.saturating_add(i as u16),
+                texture: cmd.texture().id().map_or(self.tex, |id| id as TexId),
+                blend: BlendMode::Alpha,
+                clip: Some(Scissor::from_top_left(
+                    [x0 as u32, y0 as u32],
+                    [(x1 - x0) as u32, (y1 - y0) as u32],
+                    self.size[1]
+                )),
+                ..BatchKey::default()
+            };
+            let srgb = self.srgb;
+            let tris = inds[range].iter().map(|ind| {
+                let v = &verts[*ind as usize];
+                let rgb = Color::from_rgb8([v.col[0], v.col[1], v.col[2]]).vertex_rgb(srgb);
+                BatchVertex::new([v.pos[0] / w * 2.0 - 1.0, 1.0 - v.pos[1] / h * 2.0], v.uv, rgb)
+            }).collect();
+            batch.triangles(key, tris);
+        }
+        self.ctx.clear();
+    }
+}
@@ -0,0 +1,177 @@
+use luminance::context::GraphicsContext;
+use luminance::framebuffer::{ColorSlot, DepthSlot, Framebuffer, FramebufferError};
+use luminance::pipeline::{BoundTexture, Builder, PipelineState};
+use luminance::pixel::{Depth32F, NormRGBA8UI, NormUnsigned};
+use luminance::render_state::RenderState;
+use luminance::shader::program::{Program, Uniform};
+use luminance::tess::{Mode, Tess, TessBuilder};
+use luminance::texture::{Dim2, Flat, MagFilter, MinFilter, Sampler, Wrap};
+use luminance_derive::UniformInterface;
+
+use crate::in_utils::Semantics;
+
+const POST_VS: &'static str = include_str!("../ressources/post-vs.glsl");
+const BLUR_FS: &'static str = include_str!("../ressources/post-blur-fs.glsl");
+const GRADE_FS: &'static str = include_str!("../ressources/post-grade-fs.glsl");
+const FXAA_FS: &'static str = include_str!("../ressources/post-fxaa-fs.glsl");
+
+pub type ColorFb = Framebuffer<Flat, Dim2, NormRGBA8UI, Depth32F>;
+
+#[derive(Debug, UniformInterface)]
+pub struct PostIface {
+    source: Uniform<&'static BoundTexture<'static, Flat, Dim2, NormUnsigned>>,
+    #[uniform(unbound)]
+    texel: Uniform<[f32; 2]>,
+    #[uniform(unbound)]
+    params: Uniform<[f32; 4]>
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PostEffect {
+    Blur { radius: f32 },
+    Grade { exposure: f32, contrast: f32, saturation: f32, gamma: f32 },
+    Fxaa
+}
+
+impl PostEffect {
+    pub fn blur() -> Self { PostEffect::Blur { radius: 1.5 } }
+
+    pub fn grade() -> Self {
+        PostEffect::Grade { exposure: 1.1, contrast: 1.2, saturation: 0.6, gamma: 1.0 }
+    }
+
+    fn program_idx(&self) -> usize {
+        match self {
+            PostEffect::Blur {..} => 0,
+            PostEffect::Grade {..} => 1,
+            PostEffect::Fxaa => 2
+        }
+    }
+
+    fn params(&self) -> [f32; 4] {
+        match *self {
+            PostEffect::Blur { radius } => [radius, 0.0, 0.0, 0.0],
+            PostEffect::Grade { exposure, contrast, saturation, gamma } =>
+                [exposure, contrast, saturation, gamma],
+            PostEffect::Fxaa => [0.0; 4]
+        }
+    }
+}
+
+/// Presets cycled by the demo, the first one disables the post-processing.
+pub fn presets() -> Vec<Vec<PostEffect>> {
+    vec![
+        vec![],
+        vec![PostEffect::blur()],
+        vec![PostEffect::grade()],
+        vec![PostEffect::Fxaa],
+        vec![PostEffect::blur(), PostEffect::grade(), PostEffect::Fxaa]
+    ]
+}
+
+pub fn target<C>(ctx: &mut C, size: [u32; 2]) -> Result<ColorFb, FramebufferError>
+where C: GraphicsContext {
+    let sampler = Sampler {
+        wrap_r: Wrap::ClampToEdge,
+        wrap_s: Wrap::ClampToEdge,
+        wrap_t: Wrap::ClampToEdge,
+        min_filter: MinFilter::Linear,
+        mag_filter: MagFilter::Linear,
+        ..Sampler::default()
+    };
+    Framebuffer::new(ctx, [size[0].max(1), size[1].max(1)], 0, sampler)
+}
+
+/// Offscreen targets of a window and the chain of fullscreen passes applied
+/// to them before reaching the back buffer.
+pub struct PostChain {
+    effects: Vec<PostEffect>,
+    programs: [Program<Semantics, (), PostIface>; 3],
+    fullscreen: Tess,
+    scene: ColorFb,
+    pingpong: [ColorFb; 2],
+    size: [u32; 2]
+}
+
+impl PostChain {
+    pub fn new<C>(ctx: &mut C, size: [u32; 2]) -> Result<Self, FramebufferError>
+    where C: GraphicsContext {
+        let program = |fs| Program::<Semantics, (), PostIface>::from_strings(None, POST_VS, None, fs)
+            .expect("post program creation")
+            .ignore_warnings();
+        let fullscreen = TessBuilder::new(ctx)
+            .set_vertex_nb(3)
+            .set_mode(Mode::Triangle)
+            .build()
+            .unwrap();
+        Ok(PostChain {
+            effects: Vec::new(),
+            programs: [program(BLUR_FS), program(GRADE_FS), program(FXAA_FS)],
+            fullscreen,
+            scene: target(ctx, size)?,
+            pingpong: [target(ctx, size)?, target(ctx, size)?],
+            size
+        })
+    }
+
+    pub fn effects(&self) -> &[PostEffect] { &self.effects }
+
+    pub fn set_effects(&mut self, effects: Vec<PostEffect>) { self.effects = effects; }
+
+    pub fn is_empty(&self) -> bool { self.effects.is_empty() }
+
+    /// Effects to apply, and a size to apply them at: a minimized window has none.
+    pub fn is_active(&self) -> bool { !self.is_empty() && self.size[0] > 0 && self.size[1] > 0 }
+
+    /// Follow the window size, the targets staying as they are for a 0 one.
+    pub fn resize<C>(&mut self, ctx: &mut C, size: [u32; 2]) -> Result<(), FramebufferError>
+    where C: GraphicsContext {
+        if size == self.size { return Ok(()); }
+        if size[0] == 0 || size[1] == 0 {
+            self.size = size;
+            return Ok(());
+        }
+        self.scene = target(ctx, size)?;
+        self.pingpong = [target(ctx, size)?, target(ctx, size)?];
+        self.size = size;
+        Ok(())
+    }
+
+    /// Framebuffer the scene must be rendered into when the chain is active.
+    pub fn scene(&self) -> &ColorFb { &self.scene }
+
+    /// Run every pass, the last one drawing into `output`. Nothing is drawn
+    /// if the chain isn't active.
+    pub fn apply<C>(&self, builder: &Builder<C>, output: &Framebuffer<Flat, Dim2, (), ()>)
+    where C: GraphicsContext {
+        if !self.is_active() { return; }
+        let last = self.effects.len().saturating_sub(1);
+        for (idx, effect) in self.effects.iter().enumerate() {
+            let source = if idx == 0 { &self.scene } else { &self.pingpong[(idx - 1) % 2] };
+            if idx == last { self.pass(builder, output, source, effect); }
+            else { self.pass(builder, &self.pingpong[idx % 2], source, effect); }
+        }
+    }
+
+    fn pass<C, CS, DS>(
+        &self,
+        builder: &Builder<C>,
+        dest: &Framebuffer<Flat, Dim2, CS, DS>,
+        source: &ColorFb,
+        effect: &PostEffect
+    ) where C: GraphicsContext, CS: ColorSlot<Flat, Dim2>, DS: DepthSlot<Flat, Dim2> {
+        let texel = [1.0 / self.size[0] as f32, 1.0 / self.size[1] as f32];
+        let program = &self.programs[effect.program_idx()];
+        builder.pipeline(dest, &PipelineState::default(), |pipeline, mut shd_gate| {
+            let bound = pipeline.bind_texture(source.color_slot());
+            shd_gate.shade(program, |iface, mut rdr_gate| {
+                iface.source.update(&bound);
+                iface.texel.update(texel);
+                iface.params.update(effect.params());
+                rdr_gate.render(&RenderState::default().set_depth_test(None), |mut tess_gate| {
+                    tess_gate.render(&self.fullscreen);
+                });
+            });
+        });
+    }
+}
@@ -0,0 +1,350 @@
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::in_utils::Vertex;
+
+type P2 = [f32; 2];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineJoin {
+    /// Sharp corner, turned into a bevel past `limit` times the half width.
+    Miter { limit: f32 },
+    Bevel,
+    Round
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineCap { Butt, Square, Round }
+
+fn add(a: P2, b: P2) -> P2 { [a[0] + b[0], a[1] + b[1]] }
+
+fn sub(a: P2, b: P2) -> P2 { [a[0] - b[0], a[1] - b[1]] }
+
+fn mul(a: P2, s: f32) -> P2 { [a[0] * s, a[1] * s] }
+
+fn dot(a: P2, b: P2) -> f32 { a[0] * b[0] + a[1] * b[1] }
+
+fn cross(a: P2, b: P2) -> f32 { a[0] * b[1] - a[1] * b[0] }
+
+fn normalize(a: P2) -> P2 {
+    let len = dot(a, a).sqrt();
+    if len > 0.0 { mul(a, 1.0 / len) } else { [0.0, 0.0] }
+}
+
+/// Left normal of the unit direction `d`.
+fn normal(d: P2) -> P2 { [-d[1], d[0]] }
+
+fn polar(center: P2, radius: f32, angle: f32) -> P2 {
+    [center[0] + radius * angle.cos(), center[1] + radius * angle.sin()]
+}
+
+fn signed_area(pts: &[P2]) -> f32 {
+    (0..pts.len()).map(|i| cross(pts[i], pts[(i + 1) % pts.len()])).sum::<f32>() / 2.0
+}
+
+fn in_triangle(p: P2, a: P2, b: P2, c: P2) -> bool {
+    cross(sub(b, a), sub(p, a)) >= 0.0
+        && cross(sub(c, b), sub(p, b)) >= 0.0
+        && cross(sub(a, c), sub(p, c)) >= 0.0
+}
+
+/// Tessellate 2D shapes into `Vertex` and index buffers, drawn as triangles.
+///
+/// Coordinates are in NDC unless `viewport` is set, they are then in pixels
+/// with the origin at the top left corner. The colours are written linear if
+/// `srgb` is set, for an sRGB framebuffer.
+pub struct ShapeBuilder {
+    verts: Vec<Vertex>,
+    inds: Vec<u32>,
+    color: Color,
+    srgb: bool,
+    segments: u32,
+    viewport: Option<[f32; 2]>
+}
+
+impl Default for ShapeBuilder {
+    fn default() -> Self { ShapeBuilder::new() }
+}
+
+impl ShapeBuilder {
+    pub fn new() -> Self {
+        ShapeBuilder {
+            verts: Vec::new(),
+            inds: Vec::new(),
+            color: Color::WHITE,
+            srgb: false,
+            segments: 48,
+            viewport: None
+        }
+    }
+
+    pub fn color<C: Into<Color>>(mut self, color: C) -> Self { self.color = color.into(); self }
+
+    pub fn srgb(mut self, srgb: bool) -> Self { self.srgb = srgb; self }
+
+    /// Number of segments used for a full circle.
+    pub fn segments(mut self, segments: u32) -> Self { self.segments = segments.max(3); self }
+
+    pub fn viewport(mut self, size: [u32; 2]) -> Self {
+        self.viewport = Some([size[0].max(1) as f32, size[1].max(1) as f32]);
+        self
+    }
+
+    fn push(&mut self, p: P2) -> u32 {
+        let pos = match self.viewport {
+            None => p,
+            Some([w, h]) => [p[0] / w * 2.0 - 1.0, 1.0 - p[1] / h * 2.0]
+        };
+        self.verts.push(Vertex::new(pos, self.color.vertex_rgb(self.srgb)));
+        (self.verts.len() - 1) as u32
+    }
+
+    fn tri(&mut self, a: P2, b: P2, c: P2) {
+        let (ia, ib, ic) = (self.push(a), self.push(b), self.push(c));
+        self.inds.extend_from_slice(&[ia, ib, ic]);
+    }
+
+    fn quad(&mut self, a: P2, b: P2, c: P2, d: P2) {
+        let (ia, ib, ic, id) = (self.push(a), self.push(b), self.push(c), self.push(d));
+        self.inds.extend_from_slice(&[ia, ib, ic, ia, ic, id]);
+    }
+
+    /// Fill a convex outline with a fan around its first point.
+    fn fan(&mut self, outline: &[P2]) {
+        if outline.len() < 3 { return; }
+        let first = self.push(outline[0]);
+        let mut prev = self.push(outline[1]);
+        for p in &outline[2..] {
+            let curr = self.push(*p);
+            self.inds.extend_from_slice(&[first, prev, curr]);
+            prev = curr;
+        }
+    }
+
+    fn arc_steps(&self, sweep: f32) -> u32 {
+        ((sweep.abs() / (2.0 * PI) * self.segments as f32).ceil() as u32).max(1)
+    }
+
+    fn arc_points(&self, center: P2, radius: f32, start: f32, end: f32) -> Vec<P2> {
+        let steps = self.arc_steps(end - start);
+        (0..=steps)
+            .map(|i| polar(center, radius, start + (end - start) * i as f32 / steps as f32))
+            .collect()
+    }
+
+    pub fn rect(mut self, pos: P2, size: P2) -> Self {
+        let [x, y] = pos;
+        let [w, h] = size;
+        self.quad([x, y], [x + w, y], [x + w, y + h], [x, y + h]);
+        self
+    }
+
+    pub fn rounded_rect(mut self, pos: P2, size: P2, radius: f32) -> Self {
+        let [x, y] = pos;
+        let [w, h] = size;
+        let r = radius.min(w.abs() / 2.0).min(h.abs() / 2.0).max(0.0);
+        if r == 0.0 { return self.rect(pos, size); }
+        let mut outline = Vec::new();
+        let corners = [
+            ([x + w - r, y + r], -PI / 2.0),
+            ([x + w - r, y + h - r], 0.0),
+            ([x + r, y + h - r], PI / 2.0),
+            ([x + r, y + r], PI)
+        ];
+        for (center, start) in corners.iter() {
+            outline.extend(self.arc_points(*center, r, *start, *start + PI / 2.0));
+        }
+        self.fan(&outline);
+        self
+    }
+
+    pub fn circle(mut self, center: P2, radius: f32) -> Self {
+        let mut outline = self.arc_points(center, radius, 0.0, 2.0 * PI);
+        outline.pop();
+        self.fan(&outline);
+        self
+    }
+
+    /// Stroke an arc of the circle (`center`, `radius`) from the `start`
+    /// angle to the `end` one, in radians.
+    pub fn arc(mut self, center: P2, radius: f32, start: f32, end: f32, width: f32) -> Self {
+        let inner = self.arc_points(center, radius - width / 2.0, start, end);
+        let outer = self.arc_points(center, radius + width / 2.0, start, end);
+        for i in 0..inner.len() - 1 {
+            self.quad(inner[i], outer[i], outer[i + 1], inner[i + 1]);
+        }
+        self
+    }
+
+    /// Fill a convex or concave simple polygon, by ear clipping.
+    pub fn polygon(mut self, points: &[P2]) -> Self {
+        if points.len() < 3 { return self; }
+        let mut pts = points.to_vec();
+        if signed_area(&pts) < 0.0 { pts.reverse(); }
+        let base = self.verts.len() as u32;
+        for p in &pts { self.push(*p); }
+        let mut remain: Vec<usize> = (0..pts.len()).collect();
+        while remain.len() > 3 {
+            let nb = remain.len();
+            let ear = (0..nb).find(|&i| {
+                let (a, b, c) = (pts[remain[(i + nb - 1) % nb]], pts[remain[i]], pts[remain[(i + 1) % nb]]);
+                cross(sub(b, a), sub(c, b)) > 0.0 && remain.iter()
+                    .filter(|&&j| j != remain[(i + nb - 1) % nb] && j != remain[i] && j != remain[(i + 1) % nb])
+                    .all(|&j| !in_triangle(pts[j], a, b, c))
+            });
+            // a degenerate outline has no ear left, keep what was clipped
+            let i = match ear { Some(i) => i, None => break };
+            self.inds.extend_from_slice(&[
+                base + remain[(i + nb - 1) % nb] as u32,
+                base + remain[i] as u32,
+                base + remain[(i + 1) % nb] as u32
+            ]);
+            remain.remove(i);
+        }
+        if remain.len() == 3 {
+            self.inds.extend(remain.iter().map(|i| base + *i as u32));
+        }
+        self
+    }
+
+    pub fn polyline(mut self, points: &[P2], width: f32, join: LineJoin, cap: LineCap, closed: bool) -> Self {
+        let nb = points.len();
+        if nb < 2 { return self; }
+        let hw = width / 2.0;
+        let nb_segs = if closed { nb } else { nb - 1 };
+        let dirs: Vec<P2> = (0..nb_segs)
+            .map(|i| normalize(sub(points[(i + 1) % nb], points[i])))
+            .collect();
+        for (i, d) in dirs.iter().enumerate() {
+            let n = mul(normal(*d), hw);
+            let (a, b) = (points[i], points[(i + 1) % nb]);
+            let (a, b) = match cap {
+                LineCap::Square if !closed => (
+                    if i == 0 { sub(a, mul(*d, hw)) } else { a },
+                    if i == nb_segs - 1 { add(b, mul(*d, hw)) } else { b }
+                ),
+                _ => (a, b)
+            };
+            self.quad(add(a, n), sub(a, n), sub(b, n), add(b, n));
+        }
+        let joints: Vec<usize> = if closed { (0..nb).collect() } else { (1..nb - 1).collect() };
+        for i in joints {
+            let d0 = dirs[(i + nb_segs - 1) % nb_segs];
+            let d1 = dirs[i % nb_segs];
+            self.join(points[i], d0, d1, hw, join);
+        }
+        if !closed && cap == LineCap::Round {
+            let (first, last) = (points[0], points[nb - 1]);
+            let (d0, d1) = (dirs[0], dirs[nb_segs - 1]);
+            let a0 = d0[1].atan2(d0[0]);
+            let a1 = d1[1].atan2(d1[0]);
+            let mut start = vec![first];
+            start.extend(self.arc_points(first, hw, a0 + PI / 2.0, a0 + 3.0 * PI / 2.0));
+            self.fan(&start);
+            let mut end = vec![last];
+            end.extend(self.arc_points(last, hw, a1 - PI / 2.0, a1 + PI / 2.0));
+            self.fan(&end);
+        }
+        self
+    }
+
+    fn join(&mut self, p: P2, d0: P2, d1: P2, hw: f32, join: LineJoin) {
+        let turn = cross(d0, d1);
+        if turn.abs() < 1e-6 { return; }
+        // the gap to fill is on the outer side of the turn
+        let side = if turn > 0.0 { -1.0 } else { 1.0 };
+        let n0 = mul(normal(d0), hw * side);
+        let n1 = mul(normal(d1), hw * side);
+        let (o0, o1) = (add(p, n0), add(p, n1));
+        match join {
+            LineJoin::Bevel => self.tri(p, o0, o1),
+            LineJoin::Miter { limit } => {
+                let m = normalize(add(n0, n1));
+                let len = hw / dot(m, normalize(n0)).max(1e-6);
+                if len / hw > limit { self.tri(p, o0, o1); }
+                else {
+                    let tip = add(p, mul(m, len));
+                    self.tri(p, o0, tip);
+                    self.tri(p, tip, o1);
+                }
+            }
+            LineJoin::Round => {
+                let a0 = n0[1].atan2(n0[0]);
+                let mut a1 = n1[1].atan2(n1[0]);
+                if side > 0.0 && a1 > a0 { a1 -= 2.0 * PI; }
+                if side < 0.0 && a1 < a0 { a1 += 2.0 * PI; }
+                let mut outline = vec![p];
+                outline.extend(self.arc_points(p, hw, a0, a1));
+                self.fan(&outline);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.inds.is_empty() }
+
+    pub fn build(self) -> (Vec<Vertex>, Vec<u32>) { (self.verts, self.inds) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The triangles of a built shape, by their corners.
+    fn triangles(shape: ShapeBuilder) -> Vec<[P2; 3]> {
+        let (verts, inds) = shape.build();
+        inds.chunks(3)
+            .map(|t| [verts[t[0] as usize].pos(), verts[t[1] as usize].pos(), verts[t[2] as usize].pos()])
+            .collect()
+    }
+
+    fn area(tris: &[[P2; 3]]) -> f32 {
+        tris.iter().map(|[a, b, c]| cross(sub(*b, *a), sub(*c, *a)).abs() / 2.0).sum()
+    }
+
+    #[test]
+    fn convex_polygon_fan() {
+        let hexagon: Vec<P2> = (0..6).map(|i| polar([0.0, 0.0], 0.5, i as f32 * PI / 3.0)).collect();
+        assert_eq!(triangles(ShapeBuilder::new().polygon(&hexagon)).len(), 4);
+        // clockwise, the same
+        let mut reversed = hexagon.clone();
+        reversed.reverse();
+        assert_eq!(triangles(ShapeBuilder::new().polygon(&reversed)).len(), 4);
+    }
+
+    #[test]
+    fn concave_polygon_area() {
+        let arrow = [[0.0, 0.0], [0.6, 0.4], [0.0, 0.8], [0.2, 0.4]];
+        let l_shape = [[0.0, 0.0], [0.8, 0.0], [0.8, 0.2], [0.2, 0.2], [0.2, 0.6], [0.0, 0.6]];
+        for outline in &[&arrow[..], &l_shape[..]] {
+            let tris = triangles(ShapeBuilder::new().polygon(outline));
+            assert_eq!(tris.len(), outline.len() - 2);
+            assert!((area(&tris) - signed_area(outline).abs()).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn degenerate_input() {
+        let collinear = [[0.0, 0.0], [0.2, 0.0], [0.4, 0.0], [0.6, 0.0]];
+        assert!(area(&triangles(ShapeBuilder::new().polygon(&collinear))) < 1e-6);
+        assert!(ShapeBuilder::new().polygon(&collinear[..2]).is_empty());
+        let repeated = [[0.0, 0.0], [0.0, 0.0], [0.5, 0.5], [0.5, 0.5]];
+        ShapeBuilder::new().polygon(&repeated);
+        for join in &[LineJoin::Miter { limit: 4.0 }, LineJoin::Bevel, LineJoin::Round] {
+            for cap in &[LineCap::Butt, LineCap::Square, LineCap::Round] {
+                let tris = triangles(ShapeBuilder::new().polyline(&repeated, 0.1, *join, *cap, false));
+                assert!(tris.iter().flat_map(|t| t.iter()).all(|p| p[0].is_finite() && p[1].is_finite()));
+            }
+        }
+        assert!(ShapeBuilder::new().polyline(&collinear[..1], 0.1, LineJoin::Bevel, LineCap::Butt, false).is_empty());
+        ShapeBuilder::new().circle([0.0, 0.0], 0.0).rounded_rect([0.0, 0.0], [0.0, 0.0], 0.1);
+    }
+
+    #[test]
+    fn zero_radius_rounded_rect() {
+        let rect = ShapeBuilder::new().rect([-0.5, -0.5], [1.0, 0.5]).build();
+        assert_eq!(ShapeBuilder::new().rounded_rect([-0.5, -0.5], [1.0, 0.5], 0.0).build(), rect);
+        // and the radius is clamped to the size
+        let flat = ShapeBuilder::new().rounded_rect([-0.5, -0.5], [1.0, 0.0], 0.2).build();
+        assert_eq!(flat, ShapeBuilder::new().rect([-0.5, -0.5], [1.0, 0.0]).build());
+    }
+}
@@ -0,0 +1,160 @@
+use harfbuzz_rs::{shape, Direction, Face, Font as HbFont, Owned, UnicodeBuffer};
+use rusttype::{Font, GlyphId};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+use unicode_bidi::BidiInfo;
+
+/// Glyph out of the shaper, in pixels: `advance` moves the pen, `offset`
+/// moves the glyph from the pen (y going down).
+#[derive(Copy, Clone, Debug)]
+pub struct ShapedGlyph {
+    pub face: usize,
+    pub id: GlyphId,
+    pub advance: f32,
+    pub offset: [f32; 2]
+}
+
+struct FontFace {
+    name: String,
+    font: Font<'static>,
+    shaper: Owned<HbFont<'static>>,
+    /// Ascent minus descent in font units, rusttype's pixel height.
+    height_units: f32
+}
+
+impl FontFace {
+    fn new(name: &str, bytes: &'static [u8]) -> Result<Self, String> {
+        let font = Font::from_bytes(bytes).map_err(|e| format!("{}: {}", name, e))?;
+        let v_metrics = font.v_metrics_unscaled();
+        Ok(FontFace {
+            name: name.to_string(),
+            font,
+            shaper: HbFont::new(Face::from_bytes(bytes, 0)),
+            height_units: v_metrics.ascent - v_metrics.descent
+        })
+    }
+
+    fn covers(&self, c: char) -> bool { self.font.glyph(c).id() != GlyphId(0) }
+}
+
+/// Fonts tried in order for every character: the first one is the primary
+/// font, giving the line metrics, the others are fallbacks for the scripts it
+/// doesn't cover. Cheap to clone, the faces are shared.
+#[derive(Clone)]
+pub struct FontChain {
+    faces: Rc<Vec<FontFace>>
+}
+
+impl FontChain {
+    /// The font data lives as long as the app, the shaper borrows it.
+    pub fn new(name: &str, bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(FontChain { faces: Rc::new(vec![FontFace::new(name, Box::leak(bytes.into_boxed_slice()))?]) })
+    }
+
+    pub fn from_static(name: &str, bytes: &'static [u8]) -> Result<Self, String> {
+        Ok(FontChain { faces: Rc::new(vec![FontFace::new(name, bytes)?]) })
+    }
+
+    /// Add a fallback font, must be called before the chain is cloned.
+    pub fn push(&mut self, name: &str, bytes: Vec<u8>) -> Result<(), String> {
+        let face = FontFace::new(name, Box::leak(bytes.into_boxed_slice()))?;
+        Rc::get_mut(&mut self.faces).ok_or_else(|| "font chain already shared".to_string())?.push(face);
+        Ok(())
+    }
+
+    /// Add every ttf and otf file of `dir` not loaded yet, by file name, in
+    /// name order.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), String> {
+        let mut paths: Vec<_> = fs::read_dir(dir.as_ref())
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension()
+                .and_then(|e| e.to_str())
+                .map_or(false, |e| e.eq_ignore_ascii_case("ttf") || e.eq_ignore_ascii_case("otf")))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+            if self.faces.iter().any(|face| face.name == name) { continue; }
+            let bytes = fs::read(&path).map_err(|e| format!("{}: {}", name, e))?;
+            self.push(&name, bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn primary(&self) -> &Font<'static> { &self.faces[0].font }
+
+    pub fn font(&self, face: usize) -> &Font<'static> { &self.faces[face].font }
+
+    pub fn names(&self) -> Vec<&str> { self.faces.iter().map(|face| face.name.as_str()).collect() }
+
+    /// First face covering `c`, if any.
+    fn face_for(&self, c: char) -> Option<usize> {
+        self.faces.iter().position(|face| face.covers(c))
+    }
+
+    /// Split `text` in runs of the same face. The characters no face covers,
+    /// like the combining marks or the joiners, stay with the previous run.
+    fn face_runs(&self, text: &str) -> Vec<(usize, Range<usize>)> {
+        let mut runs: Vec<(usize, Range<usize>)> = Vec::new();
+        for (i, c) in text.char_indices() {
+            let end = i + c.len_utf8();
+            let face = match (self.face_for(c), runs.last()) {
+                (Some(face), _) => face,
+                (None, Some((face, _))) => *face,
+                (None, None) => 0
+            };
+            match runs.last_mut() {
+                Some((last, range)) if *last == face => range.end = end,
+                _ => runs.push((face, i..end))
+            }
+        }
+        runs
+    }
+
+    /// Shape a run of one direction, glyphs in visual order.
+    fn shape_run(&self, text: &str, rtl: bool, px: f32) -> Vec<ShapedGlyph> {
+        let mut runs = self.face_runs(text);
+        if rtl { runs.reverse(); }
+        let mut glyphs = Vec::new();
+        for (face_idx, range) in runs {
+            let face = &self.faces[face_idx];
+            let ratio = px / face.height_units;
+            let buffer = UnicodeBuffer::new()
+                .add_str(&text[range])
+                .set_direction(if rtl { Direction::Rtl } else { Direction::Ltr })
+                .guess_segment_properties();
+            let output = shape(&face.shaper, buffer, &[]);
+            for (info, pos) in output.get_glyph_infos().iter().zip(output.get_glyph_positions()) {
+                glyphs.push(ShapedGlyph {
+                    face: face_idx,
+                    id: GlyphId(info.codepoint as _),
+                    advance: pos.x_advance as f32 * ratio,
+                    offset: [pos.x_offset as f32 * ratio, -pos.y_offset as f32 * ratio]
+                });
+            }
+        }
+        glyphs
+    }
+
+    /// Shape a line of mixed directions: it's split in bidi runs, put in
+    /// visual order, and every run is shaped with the faces covering it.
+    pub fn shape_line(&self, line: &str, px: f32) -> Vec<ShapedGlyph> {
+        let bidi = BidiInfo::new(line, None);
+        let mut glyphs = Vec::new();
+        for para in &bidi.paragraphs {
+            let (levels, runs) = bidi.visual_runs(para, para.range.clone());
+            for run in runs {
+                let rtl = levels[run.start].is_rtl();
+                glyphs.extend(self.shape_run(&line[run], rtl, px));
+            }
+        }
+        glyphs
+    }
+
+    pub fn line_width(&self, line: &str, px: f32) -> f32 {
+        self.shape_line(line, px).iter().map(|g| g.advance).sum()
+    }
+}
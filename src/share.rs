@@ -0,0 +1,56 @@
+use gl;
+use gl::types::GLsync;
+use luminance::context::GraphicsContext;
+use luminance::framebuffer::FramebufferError;
+use std::cell::Cell;
+
+use crate::postfx::{target, ColorFb};
+
+/// Offscreen target rendered by a producer window and sampled by other
+/// windows. The surfaces must have been created with shared lists (see
+/// `WinManager::create_window`), only the color texture crosses contexts, the
+/// framebuffer object itself stays bound to the producer. The targets are
+/// kept by producer window id.
+pub struct SharedTarget {
+    fb: ColorFb,
+    fence: Cell<Option<GLsync>>
+}
+
+impl SharedTarget {
+    /// Must be called with the producer context current.
+    pub fn new<C>(ctx: &mut C, size: [u32; 2]) -> Result<Self, FramebufferError>
+    where C: GraphicsContext {
+        Ok(SharedTarget { fb: target(ctx, size)?, fence: Cell::new(None) })
+    }
+
+    pub fn fb(&self) -> &ColorFb { &self.fb }
+
+    /// Insert a fence after the producer commands, the context must be the
+    /// producer one.
+    pub fn signal(&self) {
+        unsafe {
+            if let Some(old) = self.fence.take() { gl::DeleteSync(old); }
+            let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            // without a flush the fence may never reach the GPU for the other ctx
+            gl::Flush();
+            self.fence.set(Some(fence));
+        }
+    }
+
+    /// Make the current (consumer) context wait for the producer commands
+    /// before sampling the texture. The fence is kept for the other consumers
+    /// and deleted on the next signal.
+    pub fn wait(&self) {
+        if let Some(fence) = self.fence.get() {
+            unsafe { gl::WaitSync(fence, 0, gl::TIMEOUT_IGNORED); }
+        }
+    }
+}
+
+impl Drop for SharedTarget {
+    fn drop(&mut self) {
+        if let Some(fence) = self.fence.take() {
+            unsafe { gl::DeleteSync(fence); }
+        }
+    }
+}
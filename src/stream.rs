@@ -0,0 +1,56 @@
+use luminance::tess::{Tess, TessMapError};
+use luminance::vertex::Vertex;
+
+/// Tess whose vertex or instance buffer is rewritten in place by mapping it,
+/// instead of building a new tess.
+pub struct DynTess {
+    tess: Tess
+}
+
+impl DynTess {
+    pub fn new(tess: Tess) -> Self { DynTess { tess } }
+
+    pub fn tess(&self) -> &Tess { &self.tess }
+
+    pub fn update<V, F>(&mut self, f: F) -> Result<(), TessMapError>
+    where V: Vertex, F: FnOnce(&mut [V]) {
+        let mut slice = self.tess.as_slice_mut::<V>()?;
+        f(&mut *slice);
+        Ok(())
+    }
+
+    pub fn update_instances<V, F>(&mut self, f: F) -> Result<(), TessMapError>
+    where V: Vertex, F: FnOnce(&mut [V]) {
+        let mut slice = self.tess.as_inst_slice_mut::<V>()?;
+        f(&mut *slice);
+        Ok(())
+    }
+}
+
+/// Ring of dynamic tesses for streaming: every frame writes into the next
+/// slot, so the one the GPU may still be reading from the previous frames is
+/// left untouched.
+pub struct RingTess {
+    slots: Vec<DynTess>,
+    current: usize
+}
+
+impl RingTess {
+    /// `build` is called once per slot, every slot must have the same layout.
+    pub fn new<F>(nb_slots: usize, mut build: F) -> Self
+    where F: FnMut() -> Tess {
+        RingTess {
+            slots: (0..nb_slots.max(1)).map(|_| DynTess::new(build())).collect(),
+            current: 0
+        }
+    }
+
+    /// Last written slot, the one to render.
+    pub fn current(&self) -> &Tess { self.slots[self.current].tess() }
+
+    /// Move to the next slot and hand it for writing.
+    pub fn next_mut(&mut self) -> &mut DynTess {
+        self.current = (self.current + 1) % self.slots.len();
+        &mut self.slots[self.current]
+    }
+}
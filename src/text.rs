@@ -0,0 +1,511 @@
+use luminance::context::GraphicsContext;
+use luminance::shader::program::Program;
+use luminance::texture::{GenMipmaps, MagFilter, MinFilter, Sampler, Texture, Wrap};
+use rusttype::{point, Font, GlyphId, Scale};
+use std::collections::HashMap;
+
+use crate::batch::{BatchIface, BatchKey, BatchRenderer, ProgId, StyleId, StyleParams, TexId};
+use crate::color::Color;
+use crate::in_utils::Semantics;
+use crate::material::BlendMode;
+use crate::shaping::FontChain;
+use crate::texture::{RgbaTex, TexError, TexImage};
+
+pub const BERYLIUM: &'static [u8] = include_bytes!("../ressources/Berylium.ttf");
+
+const BATCH_VS: &'static str = include_str!("../ressources/batch-vs.glsl");
+const SDF_FS: &'static str = include_str!("../ressources/sdf-fs.glsl");
+
+/// Batch layer of the text, over the shapes and the sprites.
+pub const TEXT_LAYER: u16 = 16;
+
+const ATLAS_SIZE: u32 = 1024;
+
+/// Layouts kept at most, the cache is emptied past it.
+const LAYOUT_CACHE: usize = 256;
+
+/// The bundled Berylium, alone in its chain.
+pub fn bundled_fonts() -> FontChain {
+    FontChain::from_static("Berylium.ttf", BERYLIUM).expect("bundled font parsing")
+}
+
+/// Signed distance field glyphs: rasterized once at `base_px`, with the
+/// distance to the edge stored up to `spread` pixels around it, so they stay
+/// sharp at any size.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SdfOpt {
+    pub base_px: u32,
+    pub spread: u32
+}
+
+impl Default for SdfOpt {
+    fn default() -> Self { SdfOpt { base_px: 48, spread: 6 } }
+}
+
+/// Outline and drop shadow of SDF text. The widths are in fractions of the
+/// spread, up to 0.5, the offset in atlas uvs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    pub outline_color: Color,
+    pub outline_width: f32,
+    pub shadow_color: Color,
+    pub shadow_offset: [f32; 2],
+    pub shadow_softness: f32
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            outline_color: Color::BLACK.with_alpha(0.0),
+            outline_width: 0.0,
+            shadow_color: Color::BLACK.with_alpha(0.0),
+            shadow_offset: [0.0, 0.0],
+            shadow_softness: 0.0
+        }
+    }
+}
+
+impl TextStyle {
+    fn params(&self, srgb: bool) -> StyleParams {
+        StyleParams {
+            outline_color: self.outline_color.clear_color(srgb),
+            outline_width: self.outline_width.max(0.0).min(0.5),
+            shadow_color: self.shadow_color.clear_color(srgb),
+            shadow_offset: self.shadow_offset,
+            shadow_softness: self.shadow_softness.max(0.0)
+        }
+    }
+}
+
+/// Distance of every texel to the edge of the `coverage` shape, encoded
+/// from 0 (`spread` outside) to 255 (`spread` inside), the edge at 128.
+fn distance_field(coverage: &[f32], w: usize, h: usize, spread: usize) -> Vec<u8> {
+    let inside = |x: isize, y: isize| {
+        x >= 0 && y >= 0 && (x as usize) < w && (y as usize) < h && coverage[y as usize * w + x as usize] >= 0.5
+    };
+    let r = spread as isize;
+    let mut field = Vec::with_capacity(w * h);
+    for y in 0..h as isize {
+        for x in 0..w as isize {
+            let state = inside(x, y);
+            let mut dist = spread as f32;
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    if inside(x + dx, y + dy) != state {
+                        dist = dist.min(((dx * dx + dy * dy) as f32).sqrt());
+                    }
+                }
+            }
+            let signed = if state { dist } else { -dist };
+            let value = 0.5 + signed / (2.0 * spread as f32);
+            field.push((value.max(0.0).min(1.0) * 255.0) as u8);
+        }
+    }
+    field
+}
+
+/// Place of a rasterized glyph in the atlas, `offset` going from the pen
+/// position on the baseline to the glyph top left corner, in pixels of the
+/// rasterized size.
+#[derive(Copy, Clone, Debug)]
+struct GlyphEntry {
+    uvs: [[f32; 2]; 2],
+    offset: [f32; 2],
+    size: [f32; 2]
+}
+
+/// Glyphs rasterized on demand into one RGBA image, white with the coverage
+/// in the alpha channel, packed in shelves. When full the glyphs left out are
+/// skipped, and the image is cleared once the frame is uploaded: the quads
+/// already queued keep their glyphs.
+struct GlyphAtlas {
+    image: TexImage,
+    cursor: [u32; 2],
+    shelf_height: u32,
+    entries: HashMap<(usize, GlyphId, u32), Option<GlyphEntry>>,
+    sdf: Option<SdfOpt>,
+    dirty: bool,
+    full: bool
+}
+
+impl GlyphAtlas {
+    fn new(sdf: Option<SdfOpt>) -> Self {
+        GlyphAtlas {
+            image: TexImage { size: [ATLAS_SIZE, ATLAS_SIZE], texels: vec![0; (ATLAS_SIZE * ATLAS_SIZE * 4) as usize] },
+            cursor: [0, 0],
+            shelf_height: 0,
+            entries: HashMap::new(),
+            sdf,
+            dirty: true,
+            full: false
+        }
+    }
+
+    fn clear(&mut self) {
+        for texel in self.image.texels.iter_mut() { *texel = 0; }
+        self.cursor = [0, 0];
+        self.shelf_height = 0;
+        self.entries.clear();
+        self.dirty = true;
+        self.full = false;
+    }
+
+    /// Room for a `w` x `h` glyph, with a texel of padding.
+    fn allocate(&mut self, w: u32, h: u32) -> Option<[u32; 2]> {
+        if self.full { return None; }
+        if self.cursor[0] + w + 1 > ATLAS_SIZE {
+            self.cursor = [0, self.cursor[1] + self.shelf_height + 1];
+            self.shelf_height = 0;
+        }
+        if self.cursor[1] + h + 1 > ATLAS_SIZE { return None; }
+        let pos = self.cursor;
+        self.cursor[0] += w + 1;
+        self.shelf_height = self.shelf_height.max(h);
+        Some(pos)
+    }
+
+    /// Size the glyphs drawn at `px` are rasterized at.
+    fn raster_px(&self, px: u32) -> u32 {
+        match self.sdf { Some(opt) => opt.base_px, None => px }
+    }
+
+    /// Glyph `id` of the chain `face`.
+    fn glyph(&mut self, fonts: &FontChain, face: usize, id: GlyphId, px: u32) -> Option<GlyphEntry> {
+        let px = self.raster_px(px);
+        if let Some(entry) = self.entries.get(&(face, id, px)) { return *entry; }
+        let font: &Font<'static> = fonts.font(face);
+        let glyph = font.glyph(id).scaled(Scale::uniform(px as f32)).positioned(point(0.0, 0.0));
+        let entry = match glyph.pixel_bounding_box() {
+            None => None,
+            Some(bb) => {
+                let pad = self.sdf.map_or(0, |opt| opt.spread);
+                let (gw, gh) = (bb.width() as u32, bb.height() as u32);
+                let (w, h) = (gw + 2 * pad, gh + 2 * pad);
+                if w + 1 > ATLAS_SIZE || h + 1 > ATLAS_SIZE {
+                    eprintln!("glyph of {}x{} too big for the atlas", w, h);
+                    self.entries.insert((face, id, px), None);
+                    return None;
+                }
+                let mut coverage = vec![0.0; (w * h) as usize];
+                glyph.draw(|x, y, v| coverage[((y + pad) * w + x + pad) as usize] = v);
+                let alphas: Vec<u8> = match self.sdf {
+                    Some(opt) => distance_field(&coverage, w as usize, h as usize, opt.spread as usize),
+                    None => coverage.iter().map(|v| (v * 255.0) as u8).collect()
+                };
+                let pos = match self.allocate(w, h) {
+                    Some(pos) => pos,
+                    None => {
+                        // left out of the cache, it comes back after the clear
+                        if !self.full { eprintln!("glyph atlas full, cleared at the next frame"); }
+                        self.full = true;
+                        return None;
+                    }
+                };
+                for y in 0..h {
+                    for x in 0..w {
+                        let i = (((pos[1] + y) * ATLAS_SIZE + pos[0] + x) * 4) as usize;
+                        self.image.texels[i..i + 4].copy_from_slice(&[255, 255, 255, alphas[(y * w + x) as usize]]);
+                    }
+                }
+                self.dirty = true;
+                let size = ATLAS_SIZE as f32;
+                Some(GlyphEntry {
+                    uvs: [
+                        [pos[0] as f32 / size, pos[1] as f32 / size],
+                        [(pos[0] + w) as f32 / size, (pos[1] + h) as f32 / size]
+                    ],
+                    offset: [(bb.min.x - pad as i32) as f32, (bb.min.y - pad as i32) as f32],
+                    size: [w as f32, h as f32]
+                })
+            }
+        };
+        self.entries.insert((face, id, px), entry);
+        entry
+    }
+}
+
+/// Glyph quad of a laid out string, in pixels from its top left corner.
+#[derive(Copy, Clone, Debug)]
+pub struct LaidGlyph {
+    pub pos: [f32; 2],
+    pub size: [f32; 2],
+    uvs: [[f32; 2]; 2]
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TextLayout {
+    pub glyphs: Vec<LaidGlyph>,
+    pub size: [f32; 2]
+}
+
+/// Text drawn through a `BatchRenderer`: the atlas texture is registered in
+/// it, and every glyph is a quad of the `TEXT_LAYER`. The bitmap glyphs are
+/// rasterized for every size, the SDF ones once and drawn with their own
+/// program, which handles the styles.
+pub struct TextRenderer {
+    fonts: FontChain,
+    atlas: GlyphAtlas,
+    tex: TexId,
+    program: ProgId,
+    srgb: bool,
+    /// The layouts by text, size and width, their glyphs being in the atlas.
+    layouts: HashMap<(String, u32, Option<u32>), TextLayout>
+}
+
+impl TextRenderer {
+    /// Bitmap text, the ctx must be the `batch` one, `srgb` as for `ShapeBuilder`.
+    pub fn new<C>(ctx: &mut C, batch: &mut BatchRenderer, fonts: FontChain, srgb: bool) -> Result<Self, TexError>
+    where C: GraphicsContext {
+        TextRenderer::with_atlas(ctx, batch, fonts, srgb, None)
+    }
+
+    /// SDF text, see `new`.
+    pub fn new_sdf<C>(
+        ctx: &mut C,
+        batch: &mut BatchRenderer,
+        fonts: FontChain,
+        srgb: bool,
+        opt: SdfOpt
+    ) -> Result<Self, TexError>
+    where C: GraphicsContext {
+        TextRenderer::with_atlas(ctx, batch, fonts, srgb, Some(opt))
+    }
+
+    fn with_atlas<C>(
+        ctx: &mut C,
+        batch: &mut BatchRenderer,
+        fonts: FontChain,
+        srgb: bool,
+        sdf: Option<SdfOpt>
+    ) -> Result<Self, TexError>
+    where C: GraphicsContext {
+        let sampler = Sampler {
+            wrap_r: Wrap::ClampToEdge,
+            wrap_s: Wrap::ClampToEdge,
+            wrap_t: Wrap::ClampToEdge,
+            min_filter: MinFilter::Linear,
+            mag_filter: MagFilter::Linear,
+            ..Sampler::default()
+        };
+        let tex = batch.add_texture(RgbaTex::Linear(Texture::new(ctx, [ATLAS_SIZE, ATLAS_SIZE], 0, sampler)?));
+        let program = match sdf {
+            None => 0,
+            Some(_) => batch.add_program(
+                Program::<Semantics, (), BatchIface>::from_strings(None, BATCH_VS, None, SDF_FS)
+                    .expect("sdf program creation")
+                    .ignore_warnings()
+            )
+        };
+        Ok(TextRenderer { fonts, atlas: GlyphAtlas::new(sdf), tex, program, srgb, layouts: HashMap::new() })
+    }
+
+    /// Register an outline and shadow style, used by the SDF text only.
+    pub fn add_style(&self, batch: &mut BatchRenderer, style: TextStyle) -> StyleId {
+        batch.add_style(style.params(self.srgb))
+    }
+
+    /// Lay out `text` at `px` pixels high, shaped (kerning, ligatures,
+    /// joining scripts) and in visual order for the mixed directions. The
+    /// lines are broken on `\n`, and between words past `max_width` if given.
+    /// The layouts are kept until the atlas is cleared.
+    pub fn layout(&mut self, text: &str, px: f32, max_width: Option<f32>) -> TextLayout {
+        let px = px.round().max(1.0);
+        let key = (text.to_string(), px as u32, max_width.map(f32::to_bits));
+        if let Some(layout) = self.layouts.get(&key) { return layout.clone(); }
+        let v_metrics = self.fonts.primary().v_metrics(Scale::uniform(px));
+        let line_height = (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap).ceil();
+        let mut layout = TextLayout::default();
+        let mut baseline = v_metrics.ascent.ceil();
+        for line in text.lines() {
+            let wrapped = match max_width {
+                Some(max) => self.wrap(line, px, max),
+                None => vec![line.to_string()]
+            };
+            for sub in wrapped {
+                self.place(&sub, px, baseline, &mut layout);
+                baseline += line_height;
+            }
+        }
+        layout.size[1] = (baseline - line_height - v_metrics.descent.floor()).max(0.0);
+        if self.layouts.len() >= LAYOUT_CACHE { self.layouts.clear(); }
+        self.layouts.insert(key, layout.clone());
+        layout
+    }
+
+    /// Greedy split of `line` between words, a word longer than `max` stays
+    /// alone on its line. The words are shaped once each, the line widths
+    /// being their sums.
+    fn wrap(&self, line: &str, px: f32, max: f32) -> Vec<String> {
+        let space = self.fonts.line_width(" ", px);
+        let mut lines: Vec<String> = Vec::new();
+        let (mut current, mut width) = (String::new(), 0.0);
+        for word in line.split(' ') {
+            let word_width = self.fonts.line_width(word, px);
+            if current.is_empty() {
+                current = word.to_string();
+                width = word_width;
+            }
+            else if width + space + word_width > max {
+                lines.push(std::mem::replace(&mut current, word.to_string()));
+                width = word_width;
+            }
+            else {
+                current.push(' ');
+                current.push_str(word);
+                width += space + word_width;
+            }
+        }
+        lines.push(current);
+        lines
+    }
+
+    fn place(&mut self, line: &str, px: f32, baseline: f32, layout: &mut TextLayout) {
+        // the SDF glyphs are scaled from their rasterized size
+        let ratio = px / self.atlas.raster_px(px as u32) as f32;
+        let snap = |v: f32| if ratio == 1.0 { v.round() } else { v };
+        let mut pen = 0.0;
+        for glyph in self.fonts.shape_line(line, px) {
+            if let Some(entry) = self.atlas.glyph(&self.fonts, glyph.face, glyph.id, px as u32) {
+                layout.glyphs.push(LaidGlyph {
+                    pos: [
+                        snap(pen + glyph.offset[0] + entry.offset[0] * ratio),
+                        baseline + glyph.offset[1] + entry.offset[1] * ratio
+                    ],
+                    size: [entry.size[0] * ratio, entry.size[1] * ratio],
+                    uvs: entry.uvs
+                });
+            }
+            pen += glyph.advance;
+        }
+        layout.size[0] = layout.size[0].max(pen);
+    }
+
+    /// Submit `text` to `batch`, with its top left corner at `pos` in pixels
+    /// of a `viewport` sized target.
+    pub fn draw_text(
+        &mut self,
+        batch: &mut BatchRenderer,
+        viewport: [u32; 2],
+        pos: [f32; 2],
+        px: f32,
+        color: Color,
+        text: &str
+    ) -> TextLayout {
+        let layout = self.layout(text, px, None);
+        self.draw_layout(batch, viewport, pos, color, 0, &layout);
+        layout
+    }
+
+    /// Draw a laid out text, with a style from `add_style` (0 for none).
+    pub fn draw_layout(
+        &self,
+        batch: &mut BatchRenderer,
+        viewport: [u32; 2],
+        pos: [f32; 2],
+        color: Color,
+        style: StyleId,
+        layout: &TextLayout
+    ) {
+        let key = BatchKey { layer: TEXT_LAYER, style, ..BatchKey::default() };
+        self.draw_layout_with(batch, viewport, key, pos, color, layout);
+    }
+
+    /// `draw_layout` with the layer, clip and style of `key`, for the text
+    /// that isn't on `TEXT_LAYER`.
+    pub fn draw_layout_with(
+        &self,
+        batch: &mut BatchRenderer,
+        viewport: [u32; 2],
+        key: BatchKey,
+        pos: [f32; 2],
+        color: Color,
+        layout: &TextLayout
+    ) {
+        let (w, h) = (viewport[0].max(1) as f32, viewport[1].max(1) as f32);
+        let key = BatchKey { program: self.program, texture: self.tex, blend: BlendMode::Alpha, ..key };
+        let rgb = color.vertex_rgb(self.srgb);
+        for glyph in &layout.glyphs {
+            let x = (pos[0].round() + glyph.pos[0]) / w * 2.0 - 1.0;
+            // bottom of the glyph, the quad goes up from there
+            let y = 1.0 - (pos[1].round() + glyph.pos[1] + glyph.size[1]) / h * 2.0;
+            let size = [glyph.size[0] / w * 2.0, glyph.size[1] / h * 2.0];
+            let [[u0, v0], [u1, v1]] = glyph.uvs;
+            batch.quad(key, [x, y], size, [[u0, v1], [u1, v0]], rgb);
+        }
+    }
+
+    /// Upload the glyphs rasterized since the last call, before the batch
+    /// `prepare`. A full atlas is cleared afterwards, true then as glyphs
+    /// were skipped: the next frame has them.
+    pub fn flush(&mut self, batch: &BatchRenderer) -> bool {
+        if self.atlas.dirty {
+            match batch.texture(self.tex).upload_raw(GenMipmaps::No, &self.atlas.image.texels) {
+                Ok(()) => self.atlas.dirty = false,
+                Err(e) => eprintln!("error with the glyph atlas upload: {:?}", e)
+            }
+        }
+        if !self.atlas.full { return false; }
+        self.atlas.clear();
+        self.layouts.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bitmap renderer without the GL side, enough to lay out.
+    fn renderer() -> TextRenderer {
+        TextRenderer {
+            fonts: bundled_fonts(),
+            atlas: GlyphAtlas::new(None),
+            tex: 0,
+            program: 0,
+            srgb: false,
+            layouts: HashMap::new()
+        }
+    }
+
+    fn line_height(text: &TextRenderer, px: f32) -> f32 {
+        let v_metrics = text.fonts.primary().v_metrics(Scale::uniform(px));
+        (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap).ceil()
+    }
+
+    #[test]
+    fn layout_follows_the_shaped_advances() {
+        let mut text = renderer();
+        let kerned = text.fonts.line_width("AV", 32.0);
+        // the pair is kerned or not depending on the font, never pushed apart
+        assert!(kerned <= text.fonts.line_width("A", 32.0) + text.fonts.line_width("V", 32.0) + 0.01);
+        let layout = text.layout("AV", 32.0, None);
+        assert_eq!(layout.glyphs.len(), 2);
+        assert!((layout.size[0] - kerned).abs() < 0.01);
+        assert!(layout.glyphs[0].pos[0] < layout.glyphs[1].pos[0]);
+    }
+
+    #[test]
+    fn layout_breaks_lines() {
+        let mut text = renderer();
+        let one = text.layout("ab", 20.0, None);
+        let two = text.layout("ab\nab", 20.0, None);
+        assert_eq!(two.glyphs.len(), 4);
+        assert!((two.size[1] - one.size[1] - line_height(&text, 20.0)).abs() < 0.01);
+        assert_eq!(two.glyphs[2].pos[0], two.glyphs[0].pos[0]);
+        assert!(two.glyphs[2].pos[1] > two.glyphs[0].pos[1]);
+        // wrapped, the same as the explicit break
+        let max = text.fonts.line_width("ab ab", 20.0) - 1.0;
+        let wrapped = text.layout("ab ab", 20.0, Some(max));
+        assert_eq!(wrapped.size, two.size);
+    }
+
+    #[test]
+    fn wrap_between_words() {
+        let text = renderer();
+        let width = |s: &str| text.fonts.line_width(s, 20.0);
+        assert_eq!(text.wrap("aaa bbb", 20.0, width("aaa bbb")), vec!["aaa bbb"]);
+        assert_eq!(text.wrap("aaa bbb", 20.0, width("aaa bbb") - 1.0), vec!["aaa", "bbb"]);
+        // too long for any line, the word stays alone on its own
+        assert_eq!(text.wrap("a verylongword b", 20.0, width("a b")), vec!["a", "verylongword", "b"]);
+    }
+}
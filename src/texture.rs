@@ -0,0 +1,162 @@
+use luminance::context::GraphicsContext;
+use luminance::pipeline::{BoundTexture, Pipeline};
+use luminance::pixel::{NormRGBA8UI, NormUnsigned, SRGBA8UI};
+use luminance::shader::program::Uniform;
+use luminance::texture::{
+    Dim2, Flat, GenMipmaps, MagFilter, MinFilter,
+    Sampler, Texture, TextureError, Wrap
+};
+use luminance_derive::UniformInterface;
+use std::fmt;
+use std::path::Path;
+
+/// RGBA8 texture, sRGB encoded for the images drawn to an sRGB framebuffer:
+/// the GPU decodes the texels when sampling, before the filtering. Both are
+/// sampled as normalized floats.
+pub enum RgbaTex {
+    Linear(Texture<Flat, Dim2, NormRGBA8UI>),
+    Srgb(Texture<Flat, Dim2, SRGBA8UI>)
+}
+
+impl RgbaTex {
+    pub fn bind<'a>(&'a self, pipeline: &'a Pipeline<'a>) -> BoundTexture<'a, Flat, Dim2, NormUnsigned> {
+        match self {
+            RgbaTex::Linear(tex) => pipeline.bind_texture(tex),
+            RgbaTex::Srgb(tex) => pipeline.bind_texture(tex)
+        }
+    }
+
+    pub fn upload_raw(&self, gen: GenMipmaps, texels: &[u8]) -> Result<(), TextureError> {
+        match self {
+            RgbaTex::Linear(tex) => tex.upload_raw(gen, texels),
+            RgbaTex::Srgb(tex) => tex.upload_raw(gen, texels)
+        }
+    }
+}
+
+#[derive(Debug, UniformInterface)]
+pub struct TexIface {
+    pub tex: Uniform<&'static BoundTexture<'static, Flat, Dim2, NormUnsigned>>
+}
+
+#[derive(Debug)]
+pub enum TexError {
+    ImageError(image::ImageError),
+    TextureError(TextureError),
+    TexInternError(String)
+}
+
+impl fmt::Display for TexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            TexError::ImageError(ref e) => write!(f, "Image loading error: {}", e),
+            TexError::TextureError(ref e) => write!(f, "Texture creation error: {:?}", e),
+            TexError::TexInternError(ref e) => write!(f, "Texture intern error: {}", e)
+        }
+    }
+}
+
+impl From<image::ImageError> for TexError {
+    fn from(e: image::ImageError) -> Self { TexError::ImageError(e) }
+}
+
+impl From<TextureError> for TexError {
+    fn from(e: TextureError) -> Self { TexError::TextureError(e) }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TexFilter { Nearest, Linear, Trilinear }
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TexOpt {
+    pub filter: TexFilter,
+    pub wrap: Wrap
+}
+
+impl Default for TexOpt {
+    fn default() -> Self { TexOpt { filter: TexFilter::Trilinear, wrap: Wrap::Repeat } }
+}
+
+impl TexOpt {
+    pub fn parse_filter(name: &str) -> Result<TexFilter, TexError> {
+        match name {
+            "nearest" => Ok(TexFilter::Nearest),
+            "linear" => Ok(TexFilter::Linear),
+            "trilinear" => Ok(TexFilter::Trilinear),
+            _ => Err(TexError::TexInternError(format!("unknown filter: {}", name)))
+        }
+    }
+
+    pub fn parse_wrap(name: &str) -> Result<Wrap, TexError> {
+        match name {
+            "clamp" => Ok(Wrap::ClampToEdge),
+            "repeat" => Ok(Wrap::Repeat),
+            "mirror" => Ok(Wrap::MirroredRepeat),
+            _ => Err(TexError::TexInternError(format!("unknown wrap: {}", name)))
+        }
+    }
+
+    pub fn sampler(&self) -> Sampler {
+        let (min_filter, mag_filter) = match self.filter {
+            TexFilter::Nearest => (MinFilter::Nearest, MagFilter::Nearest),
+            TexFilter::Linear => (MinFilter::Linear, MagFilter::Linear),
+            TexFilter::Trilinear => (MinFilter::LinearMipmapLinear, MagFilter::Linear)
+        };
+        Sampler {
+            wrap_r: self.wrap,
+            wrap_s: self.wrap,
+            wrap_t: self.wrap,
+            min_filter,
+            mag_filter,
+            ..Sampler::default()
+        }
+    }
+}
+
+/// RGBA8 image kept on the CPU side, flipped so the first row is the bottom one
+/// as expected by OpenGL.
+#[derive(Clone, Debug)]
+pub struct TexImage {
+    pub size: [u32; 2],
+    pub texels: Vec<u8>
+}
+
+impl TexImage {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<TexImage, TexError> {
+        let img = image::open(path)?.flipv().to_rgba();
+        let (w, h) = img.dimensions();
+        Ok(TexImage { size: [w, h], texels: img.into_raw() })
+    }
+
+    pub fn checker(size: u32, cells: u32, cols: [[u8; 4]; 2]) -> TexImage {
+        let cell = (size / cells.max(1)).max(1);
+        let mut texels = Vec::with_capacity((size * size * 4) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                texels.extend_from_slice(&cols[((x / cell + y / cell) % 2) as usize]);
+            }
+        }
+        TexImage { size: [size, size], texels }
+    }
+
+    pub fn mipmaps(&self) -> usize {
+        let max = self.size[0].max(self.size[1]).max(1);
+        (32 - max.leading_zeros() - 1) as usize
+    }
+
+    /// Upload the image, `srgb` for an sRGB framebuffer: the texels are then
+    /// sRGB encoded, gl decoding them when sampled and encoding what it
+    /// writes. Not for the coverage textures, like the glyph atlases.
+    pub fn upload<C>(&self, ctx: &mut C, opt: &TexOpt, srgb: bool) -> Result<RgbaTex, TexError>
+    where C: GraphicsContext {
+        let mipmaps = match opt.filter {
+            TexFilter::Trilinear => self.mipmaps(),
+            TexFilter::Nearest | TexFilter::Linear => 0
+        };
+        let tex = if srgb { RgbaTex::Srgb(Texture::new(ctx, self.size, mipmaps, opt.sampler())?) }
+            else { RgbaTex::Linear(Texture::new(ctx, self.size, mipmaps, opt.sampler())?) };
+        let gen = if mipmaps > 0 { GenMipmaps::Yes } else { GenMipmaps::No };
+        tex.upload_raw(gen, &self.texels)?;
+        Ok(tex)
+    }
+}
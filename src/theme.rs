@@ -0,0 +1,144 @@
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::color::Color;
+use crate::gui::UiStyle;
+use crate::shaping::FontChain;
+use crate::widgets::TreeStyle;
+
+/// Font of the themes not giving one, the bundled font.
+pub const DEFAULT_FONT: &str = "Berylium.ttf";
+
+const BUILTIN: [&str; 3] = [
+    include_str!("../ressources/themes/dark.ron"),
+    include_str!("../ressources/themes/light.ron"),
+    include_str!("../ressources/themes/high-contrast.ron")
+];
+
+#[derive(Debug)]
+pub enum ThemeError {
+    IoError(std::io::Error),
+    RonError(ron::de::Error),
+    FontError(String)
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ThemeError::IoError(ref e) => write!(f, "Theme file error: {}", e),
+            ThemeError::RonError(ref e) => write!(f, "Theme RON error: {}", e),
+            ThemeError::FontError(ref e) => write!(f, "Theme font error: {}", e)
+        }
+    }
+}
+
+impl From<std::io::Error> for ThemeError {
+    fn from(e: std::io::Error) -> Self { ThemeError::IoError(e) }
+}
+
+impl From<ron::de::Error> for ThemeError {
+    fn from(e: ron::de::Error) -> Self { ThemeError::RonError(e) }
+}
+
+/// Colours of a theme, hex codes or colour names in the files.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+pub struct ThemeColors {
+    pub panel: Color,
+    pub title_bar: Color,
+    pub widget: Color,
+    pub hot: Color,
+    pub active: Color,
+    pub accent: Color,
+    pub text: Color,
+    pub border: Color
+}
+
+/// Look of the UIs drawn in the windows, the immediate mode GUI and the
+/// widget trees alike, read from a RON file:
+///
+/// ```ron
+/// (
+///     name: "dark",
+///     font: "Berylium.ttf",
+///     text_px: 16.0,
+///     padding: 8.0,
+///     radius: 4.0,
+///     border_width: 1.0,
+///     colors: (panel: "#202028", title_bar: "#34344a", widget: "#3a3a48", hot: "#4a4a60",
+///         active: "#5a5a78", accent: "coral", text: "#e8e8f0", border: "#50506a"),
+/// )
+/// ```
+///
+/// The font is a file of `ressources/` or a path.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    #[serde(default)]
+    pub font: Option<String>,
+    pub text_px: f32,
+    pub padding: f32,
+    pub radius: f32,
+    pub border_width: f32,
+    pub colors: ThemeColors
+}
+
+impl Theme {
+    /// The dark, light and high contrast themes, in this order.
+    pub fn builtin() -> Vec<Theme> {
+        BUILTIN.iter().map(|src| ron::de::from_str(src).expect("builtin theme")).collect()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Theme, ThemeError> {
+        let theme: Theme = ron::de::from_str(&fs::read_to_string(path)?)?;
+        Ok(theme)
+    }
+
+    pub fn font_name(&self) -> &str { self.font.as_ref().map_or(DEFAULT_FONT, |f| f.as_str()) }
+
+    /// Fonts of the theme: `fonts` if it keeps the default font, else its
+    /// font followed by the fallbacks of `ressources/`.
+    pub fn fonts(&self, fonts: &FontChain) -> Result<FontChain, ThemeError> {
+        let name = self.font_name();
+        if name == DEFAULT_FONT { return Ok(fonts.clone()); }
+        let in_ressources = Path::new("ressources").join(name);
+        let path = if in_ressources.is_file() { in_ressources } else { Path::new(name).to_path_buf() };
+        let mut chain = FontChain::new(name, fs::read(&path)?).map_err(ThemeError::FontError)?;
+        if Path::new("ressources").is_dir() { chain.load_dir("ressources").map_err(ThemeError::FontError)?; }
+        Ok(chain)
+    }
+
+    pub fn ui_style(&self) -> UiStyle {
+        let c = &self.colors;
+        UiStyle {
+            panel: c.panel,
+            title_bar: c.title_bar,
+            widget: c.widget,
+            hot: c.hot,
+            active: c.active,
+            accent: c.accent,
+            text: c.text,
+            border: c.border,
+            text_px: self.text_px,
+            row_height: (self.text_px + self.padding * 1.25).round(),
+            padding: self.padding,
+            rounding: self.radius,
+            border_width: self.border_width
+        }
+    }
+
+    pub fn tree_style(&self) -> TreeStyle {
+        let c = &self.colors;
+        TreeStyle {
+            text: c.text,
+            panel: c.panel,
+            buttons: [c.widget, c.hot, c.active],
+            border: c.border,
+            text_px: self.text_px,
+            padding: self.padding * 0.75,
+            radius: self.radius,
+            border_width: self.border_width
+        }
+    }
+}
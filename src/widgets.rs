@@ -0,0 +1,560 @@
+use glutin::event::{ElementState, MouseButton, WindowEvent};
+use std::f32;
+
+use crate::batch::{BatchKey, BatchRenderer};
+use crate::color::Color;
+use crate::material::BlendMode;
+use crate::shapes::ShapeBuilder;
+use crate::text::TextRenderer;
+
+/// Batch layer of the widget backgrounds, their text is on the next one.
+/// Over the scene text, under the immediate mode GUI.
+pub const TREE_LAYER: u16 = 24;
+
+pub type NodeId = usize;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction { Row, Column }
+
+/// Placement of the children on the cross axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Align { Start, Center, End, Stretch }
+
+/// Placement of the children on the main axis, when they don't grow.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Justify { Start, Center, End, SpaceBetween }
+
+/// Flexbox like layout of a widget: how it lays out its children, and how
+/// its parent sizes it (`grow`, `size`, `min`, `max`).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Flex {
+    pub direction: Direction,
+    pub align: Align,
+    pub justify: Justify,
+    pub padding: f32,
+    pub gap: f32,
+    /// Share of the free space of the parent main axis.
+    pub grow: f32,
+    /// Fixed size, instead of the measured one.
+    pub size: [Option<f32>; 2],
+    pub min: [f32; 2],
+    pub max: [f32; 2]
+}
+
+impl Default for Flex {
+    fn default() -> Self {
+        Flex {
+            direction: Direction::Column,
+            align: Align::Stretch,
+            justify: Justify::Start,
+            padding: 0.0,
+            gap: 0.0,
+            grow: 0.0,
+            size: [None, None],
+            min: [0.0, 0.0],
+            max: [f32::INFINITY, f32::INFINITY]
+        }
+    }
+}
+
+impl Flex {
+    pub fn row() -> Self { Flex { direction: Direction::Row, ..Flex::default() } }
+
+    pub fn column() -> Self { Flex::default() }
+
+    pub fn align(mut self, align: Align) -> Self { self.align = align; self }
+
+    pub fn justify(mut self, justify: Justify) -> Self { self.justify = justify; self }
+
+    pub fn padding(mut self, padding: f32) -> Self { self.padding = padding; self }
+
+    pub fn gap(mut self, gap: f32) -> Self { self.gap = gap; self }
+
+    pub fn grow(mut self, grow: f32) -> Self { self.grow = grow; self }
+
+    pub fn width(mut self, width: f32) -> Self { self.size[0] = Some(width); self }
+
+    pub fn height(mut self, height: f32) -> Self { self.size[1] = Some(height); self }
+
+    pub fn min(mut self, min: [f32; 2]) -> Self { self.min = min; self }
+
+    pub fn max(mut self, max: [f32; 2]) -> Self { self.max = max; self }
+
+    fn main_axis(&self) -> usize {
+        match self.direction { Direction::Row => 0, Direction::Column => 1 }
+    }
+
+    fn clamp(&self, size: f32, axis: usize) -> f32 {
+        self.size[axis].unwrap_or(size).max(self.min[axis]).min(self.max[axis])
+    }
+}
+
+/// Look of the tree widgets. The panels and buttons take their padding
+/// from here, instead of their `Flex`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TreeStyle {
+    pub text: Color,
+    pub panel: Color,
+    /// Button backgrounds: idle, hovered, pressed.
+    pub buttons: [Color; 3],
+    pub border: Color,
+    pub text_px: f32,
+    pub padding: f32,
+    pub radius: f32,
+    /// Panel and button border, none at 0.
+    pub border_width: f32
+}
+
+impl Default for TreeStyle {
+    fn default() -> Self {
+        TreeStyle {
+            text: Color::WHITE,
+            panel: Color::from_hex("#202028").unwrap(),
+            buttons: [
+                Color::from_hex("#3a3a48").unwrap(),
+                Color::from_hex("#4a4a60").unwrap(),
+                Color::from_hex("#5a5a78").unwrap()
+            ],
+            border: Color::from_hex("#50506a").unwrap(),
+            text_px: 18.0,
+            padding: 6.0,
+            radius: 0.0,
+            border_width: 0.0
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WidgetKind {
+    Container,
+    /// Container drawn with the panel colours of the style.
+    Panel,
+    Label(String),
+    Button(String)
+}
+
+/// Node of a `WidgetTree`, built with the constructors then added to a
+/// parent.
+#[derive(Clone, Debug)]
+pub struct Widget {
+    pub kind: WidgetKind,
+    pub flex: Flex,
+    pub background: Option<Color>,
+    /// Text colour, the style one if none.
+    pub color: Option<Color>,
+    children: Vec<NodeId>,
+    /// Measured text size, for the labels and buttons.
+    content: [f32; 2],
+    pref: [f32; 2],
+    rect: [f32; 4]
+}
+
+impl Widget {
+    fn new(kind: WidgetKind, flex: Flex) -> Self {
+        Widget {
+            kind,
+            flex,
+            background: None,
+            color: None,
+            children: Vec::new(),
+            content: [0.0, 0.0],
+            pref: [0.0, 0.0],
+            rect: [0.0, 0.0, 0.0, 0.0]
+        }
+    }
+
+    pub fn container(flex: Flex) -> Self { Widget::new(WidgetKind::Container, flex) }
+
+    pub fn panel(flex: Flex) -> Self { Widget::new(WidgetKind::Panel, flex) }
+
+    pub fn label(text: &str) -> Self { Widget::new(WidgetKind::Label(text.to_string()), Flex::default()) }
+
+    pub fn button(text: &str) -> Self {
+        Widget::new(WidgetKind::Button(text.to_string()), Flex::default())
+    }
+
+    pub fn flex(mut self, flex: Flex) -> Self { self.flex = flex; self }
+
+    pub fn background(mut self, color: Color) -> Self { self.background = Some(color); self }
+
+    pub fn color(mut self, color: Color) -> Self { self.color = Some(color); self }
+
+    fn is_button(&self) -> bool {
+        match self.kind { WidgetKind::Button(_) => true, _ => false }
+    }
+
+    /// Drawn with the style panel or button background.
+    fn is_themed(&self) -> bool {
+        match self.kind { WidgetKind::Panel | WidgetKind::Button(_) => true, _ => false }
+    }
+
+    fn text(&self) -> Option<&str> {
+        match &self.kind {
+            WidgetKind::Container | WidgetKind::Panel => None,
+            WidgetKind::Label(text) | WidgetKind::Button(text) => Some(text)
+        }
+    }
+}
+
+fn contains(rect: [f32; 4], p: [f32; 2]) -> bool {
+    p[0] >= rect[0] && p[1] >= rect[1] && p[0] < rect[0] + rect[2] && p[1] < rect[1] + rect[3]
+}
+
+/// Retained widgets of a window, laid out again only when they change or the
+/// window is resized. The tree keeps the dirty state of the window: any
+/// change of a widget marks it, and the window asks for a redraw only then.
+pub struct WidgetTree {
+    nodes: Vec<Widget>,
+    size: [f32; 2],
+    style: TreeStyle,
+    srgb: bool,
+    layout_dirty: bool,
+    paint_dirty: bool,
+    cursor: Option<[f32; 2]>,
+    hovered: Option<NodeId>,
+    pressed: Option<NodeId>,
+    clicked: Vec<NodeId>
+}
+
+impl WidgetTree {
+    /// Tree with a root container of `flex` covering a window of `size`.
+    pub fn new(flex: Flex, size: [u32; 2], srgb: bool) -> Self {
+        WidgetTree {
+            nodes: vec![Widget::container(flex)],
+            size: [size[0] as f32, size[1] as f32],
+            style: TreeStyle::default(),
+            srgb,
+            layout_dirty: true,
+            paint_dirty: true,
+            cursor: None,
+            hovered: None,
+            pressed: None,
+            clicked: Vec::new()
+        }
+    }
+
+    pub fn root(&self) -> NodeId { 0 }
+
+    pub fn add(&mut self, parent: NodeId, widget: Widget) -> NodeId {
+        self.nodes.push(widget);
+        let id = self.nodes.len() - 1;
+        self.nodes[parent].children.push(id);
+        self.relayout();
+        id
+    }
+
+    pub fn get(&self, id: NodeId) -> &Widget { &self.nodes[id] }
+
+    /// Change the text of a label or button, only a new text dirties the tree.
+    pub fn set_text(&mut self, id: NodeId, text: &str) {
+        match &mut self.nodes[id].kind {
+            WidgetKind::Label(current) | WidgetKind::Button(current) if current != text => {
+                *current = text.to_string();
+            }
+            _ => return
+        }
+        self.relayout();
+    }
+
+    pub fn set_background(&mut self, id: NodeId, background: Option<Color>) {
+        if self.nodes[id].background != background {
+            self.nodes[id].background = background;
+            self.invalidate();
+        }
+    }
+
+    pub fn style(&self) -> &TreeStyle { &self.style }
+
+    /// Restyle the whole tree, a theme switch.
+    pub fn set_style(&mut self, style: TreeStyle) {
+        if self.style != style {
+            self.style = style;
+            self.relayout();
+        }
+    }
+
+    fn padding(&self, id: NodeId) -> f32 {
+        let widget = &self.nodes[id];
+        if widget.is_themed() { self.style.padding } else { widget.flex.padding }
+    }
+
+    /// Rect of a widget in window pixels, from the last layout.
+    pub fn rect(&self, id: NodeId) -> [f32; 4] { self.nodes[id].rect }
+
+    /// To call on `WindowEvent::Resized`.
+    pub fn resize(&mut self, size: [u32; 2]) {
+        let size = [size[0] as f32, size[1] as f32];
+        if size != self.size {
+            self.size = size;
+            self.relayout();
+        }
+    }
+
+    fn relayout(&mut self) {
+        self.layout_dirty = true;
+        self.paint_dirty = true;
+    }
+
+    /// Something drawn with the tree changed outside of it, the window needs
+    /// a redraw.
+    pub fn invalidate(&mut self) { self.paint_dirty = true; }
+
+    pub fn needs_redraw(&self) -> bool { self.paint_dirty }
+
+    /// The redraw is requested, the tree is clean until the next change.
+    pub fn painted(&mut self) { self.paint_dirty = false; }
+
+    /// Lay out the tree if it changed, `measure` giving the size of a text at
+    /// a pixel height.
+    pub fn layout(&mut self, measure: &mut dyn FnMut(&str, f32) -> [f32; 2]) {
+        if !self.layout_dirty { return; }
+        self.measure(0, measure);
+        self.arrange(0, [0.0, 0.0, self.size[0], self.size[1]]);
+        self.layout_dirty = false;
+        // the widgets moved under the cursor
+        self.hovered = self.cursor.and_then(|p| self.hit(p));
+    }
+
+    /// Preferred size of a widget, bottom up.
+    fn measure(&mut self, id: NodeId, measure: &mut dyn FnMut(&str, f32) -> [f32; 2]) -> [f32; 2] {
+        let flex = self.nodes[id].flex;
+        let content = match self.nodes[id].text() {
+            Some(text) => {
+                let size = measure(text, self.style.text_px);
+                self.nodes[id].content = size;
+                size
+            }
+            None => {
+                let children = self.nodes[id].children.clone();
+                let (main, cross) = (flex.main_axis(), 1 - flex.main_axis());
+                let mut size = [0.0, 0.0];
+                for child in &children {
+                    let pref = self.measure(*child, measure);
+                    size[main] += pref[main];
+                    size[cross] = f32::max(size[cross], pref[cross]);
+                }
+                size[main] += flex.gap * children.len().saturating_sub(1) as f32;
+                size
+            }
+        };
+        let padding = self.padding(id);
+        let pref = [
+            flex.clamp(content[0] + 2.0 * padding, 0),
+            flex.clamp(content[1] + 2.0 * padding, 1)
+        ];
+        self.nodes[id].pref = pref;
+        pref
+    }
+
+    /// Place a widget in `rect`, then its children in it, top down.
+    fn arrange(&mut self, id: NodeId, rect: [f32; 4]) {
+        self.nodes[id].rect = rect;
+        let flex = self.nodes[id].flex;
+        let children = self.nodes[id].children.clone();
+        if children.is_empty() { return; }
+        let (main, cross) = (flex.main_axis(), 1 - flex.main_axis());
+        let padding = self.padding(id);
+        let origin = [rect[0] + padding, rect[1] + padding];
+        let inner = [(rect[2] - 2.0 * padding).max(0.0), (rect[3] - 2.0 * padding).max(0.0)];
+        let mut sizes: Vec<f32> = children.iter().map(|c| self.nodes[*c].pref[main]).collect();
+        let gaps = flex.gap * (children.len() - 1) as f32;
+        let free = inner[main] - sizes.iter().sum::<f32>() - gaps;
+        let grow: f32 = children.iter().map(|c| self.nodes[*c].flex.grow).sum();
+        let (mut pos, spacing) = if grow > 0.0 && free > 0.0 {
+            for (size, child) in sizes.iter_mut().zip(&children) {
+                let child = &self.nodes[*child].flex;
+                *size = child.clamp(*size + free * child.grow / grow, main);
+            }
+            (0.0, flex.gap)
+        }
+        else {
+            let free = free.max(0.0);
+            match flex.justify {
+                Justify::Start => (0.0, flex.gap),
+                Justify::Center => (free / 2.0, flex.gap),
+                Justify::End => (free, flex.gap),
+                Justify::SpaceBetween if children.len() > 1 =>
+                    (0.0, flex.gap + free / (children.len() - 1) as f32),
+                Justify::SpaceBetween => (0.0, flex.gap)
+            }
+        };
+        for (child, size) in children.iter().zip(sizes) {
+            let child_flex = self.nodes[*child].flex;
+            let pref = self.nodes[*child].pref[cross];
+            let cross_size = match flex.align {
+                Align::Stretch => child_flex.clamp(inner[cross], cross),
+                _ => pref.min(inner[cross])
+            };
+            let offset = match flex.align {
+                Align::Start | Align::Stretch => 0.0,
+                Align::Center => (inner[cross] - cross_size) / 2.0,
+                Align::End => inner[cross] - cross_size
+            };
+            let mut child_rect = [0.0; 4];
+            child_rect[main] = origin[main] + pos;
+            child_rect[cross] = origin[cross] + offset;
+            child_rect[2 + main] = size;
+            child_rect[2 + cross] = cross_size;
+            self.arrange(*child, child_rect);
+            pos += size + spacing;
+        }
+    }
+
+    /// Deepest button under `p`.
+    fn hit(&self, p: [f32; 2]) -> Option<NodeId> {
+        self.nodes.iter().enumerate().rev()
+            .find(|(_, w)| w.is_button() && contains(w.rect, p))
+            .map(|(id, _)| id)
+    }
+
+    /// True if `p` is on a widget drawn opaque, the events there aren't for
+    /// the scene.
+    fn blocks(&self, p: [f32; 2]) -> bool {
+        self.nodes.iter().skip(1)
+            .any(|w| (w.background.is_some() || w.is_themed()) && contains(w.rect, p))
+    }
+
+    /// Feed a window event, returns true if the tree used it. The clicked
+    /// buttons are then given by `take_clicked`.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let p = [position.x as f32, position.y as f32];
+                self.cursor = Some(p);
+                let hovered = self.hit(p);
+                if hovered != self.hovered {
+                    self.hovered = hovered;
+                    self.invalidate();
+                }
+                self.pressed.is_some() || self.blocks(p)
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                if self.hovered.take().is_some() { self.invalidate(); }
+                false
+            }
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+                if self.hovered.is_some() {
+                    self.pressed = self.hovered;
+                    self.invalidate();
+                }
+                self.cursor.map_or(false, |p| self.blocks(p))
+            }
+            WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. } => {
+                match self.pressed.take() {
+                    Some(id) => {
+                        if self.hovered == Some(id) { self.clicked.push(id); }
+                        self.invalidate();
+                        true
+                    }
+                    None => false
+                }
+            }
+            _ => false
+        }
+    }
+
+    /// Next button clicked since the last call.
+    pub fn take_clicked(&mut self) -> Option<NodeId> {
+        if self.clicked.is_empty() { None } else { Some(self.clicked.remove(0)) }
+    }
+
+    /// Submit the tree to `batch`, in tree order, after a `layout`.
+    pub fn draw(&self, batch: &mut BatchRenderer, text: &mut TextRenderer) {
+        let size = [self.size[0] as u32, self.size[1] as u32];
+        let mut shapes = ShapeBuilder::new().viewport(size).srgb(self.srgb);
+        let mut stack = vec![0];
+        let mut labels = Vec::new();
+        while let Some(id) = stack.pop() {
+            let widget = &self.nodes[id];
+            let [x, y, w, h] = widget.rect;
+            let style = &self.style;
+            let fill = match widget.kind {
+                WidgetKind::Button(_) if self.pressed == Some(id) => Some(style.buttons[2]),
+                WidgetKind::Button(_) if self.hovered == Some(id) => Some(style.buttons[1]),
+                WidgetKind::Button(_) => Some(widget.background.unwrap_or(style.buttons[0])),
+                WidgetKind::Panel => Some(widget.background.unwrap_or(style.panel)),
+                _ => widget.background
+            };
+            if let Some(fill) = fill {
+                let (r, b) = if widget.is_themed() { (style.radius, style.border_width) } else { (0.0, 0.0) };
+                if b > 0.0 { shapes = shapes.color(style.border).rounded_rect([x, y], [w, h], r); }
+                shapes = shapes.color(fill).rounded_rect([x + b, y + b], [w - 2.0 * b, h - 2.0 * b], (r - b).max(0.0));
+            }
+            if let Some(content) = widget.text() {
+                let [cw, ch] = widget.content;
+                let tx = match widget.kind {
+                    WidgetKind::Button(_) => x + (w - cw) / 2.0,
+                    _ => x + widget.flex.padding
+                };
+                labels.push(([tx, y + (h - ch) / 2.0], widget.color.unwrap_or(self.style.text), content));
+            }
+            stack.extend(widget.children.iter().rev());
+        }
+        let key = BatchKey { layer: TREE_LAYER, blend: BlendMode::Alpha, ..BatchKey::default() };
+        batch.shape(key, shapes);
+        let text_key = BatchKey { layer: TREE_LAYER + 1, ..key };
+        for (pos, color, content) in labels {
+            let layout = text.layout(content, self.style.text_px, None);
+            text.draw_layout_with(batch, size, text_key, pos, color, &layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ten pixels a character, twenty high.
+    fn layout(tree: &mut WidgetTree) {
+        tree.layout(&mut |text, _| [text.len() as f32 * 10.0, 20.0]);
+    }
+
+    #[test]
+    fn row_layout() {
+        let mut tree = WidgetTree::new(Flex::row(), [400, 100], false);
+        let fixed = tree.add(0, Widget::container(Flex::default().width(50.0)));
+        let capped = tree.add(0, Widget::container(Flex::default().grow(1.0).max([100.0, f32::INFINITY])));
+        let rest = tree.add(0, Widget::container(Flex::default().grow(1.0).min([80.0, 0.0]).max([f32::INFINITY, 60.0])));
+        layout(&mut tree);
+        assert_eq!(tree.rect(fixed), [0.0, 0.0, 50.0, 100.0]);
+        // the free space is shared, then the sizes are clamped
+        assert_eq!(tree.rect(capped), [50.0, 0.0, 100.0, 100.0]);
+        assert_eq!(tree.rect(rest), [150.0, 0.0, 215.0, 60.0]);
+    }
+
+    #[test]
+    fn column_layout() {
+        let flex = Flex::column().justify(Justify::Center).align(Align::Center).gap(10.0);
+        let mut tree = WidgetTree::new(flex, [200, 300], false);
+        let short = tree.add(0, Widget::label("abc"));
+        let long = tree.add(0, Widget::label("a long label").flex(Flex::default().max([60.0, f32::INFINITY])));
+        let boxed = tree.add(0, Widget::container(Flex::default().min([50.0, 40.0])));
+        layout(&mut tree);
+        assert_eq!(tree.rect(short), [85.0, 100.0, 30.0, 20.0]);
+        assert_eq!(tree.rect(long), [70.0, 130.0, 60.0, 20.0]);
+        assert_eq!(tree.rect(boxed), [75.0, 160.0, 50.0, 40.0]);
+    }
+
+    #[test]
+    fn set_dirties_on_change() {
+        let mut trees: Vec<_> = (0..2).map(|_| WidgetTree::new(Flex::column(), [100, 100], false)).collect();
+        let label = trees[0].add(0, Widget::label("a"));
+        trees[1].add(0, Widget::label("a"));
+        for tree in trees.iter_mut() {
+            layout(tree);
+            tree.painted();
+        }
+        trees[0].set_text(label, "a");
+        trees[0].set_text(0, "a");
+        trees[0].set_background(label, None);
+        assert!(!trees[0].needs_redraw());
+        // a new background is painted without a layout
+        trees[0].set_background(label, Some(Color::BLACK));
+        assert!(trees[0].needs_redraw() && !trees[0].layout_dirty);
+        trees[0].painted();
+        trees[0].set_text(label, "b");
+        assert!(trees[0].needs_redraw() && trees[0].layout_dirty);
+        assert!(!trees[1].needs_redraw() && !trees[1].layout_dirty);
+    }
+}
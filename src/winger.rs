@@ -59,6 +59,7 @@ impl From<ContextError> for WinError {
 pub struct WinSurface {
     //win_ctx: WindowedContext<PossiblyCurrent>,
     win_ctx: CtxCurrWrapper,
+    srgb: bool,
     gfx_state: Rc<RefCell<GraphicsState>>
 }
 
@@ -76,6 +77,20 @@ impl WinSurface {
         //
         //manager: Option<&mut WinManager>
         //
+    ) -> Result<Self, WinError> {
+        WinSurface::new_shared(el, dim, title, win_opt, None, false)
+    }
+
+    /// Same as `new`, but the GL objects (textures, buffers, syncs) are shared
+    /// with the `shared` surface context. With `srgb`, the default framebuffer
+    /// encodes the linear values written into it.
+    pub fn new_shared<T>(
+        el: &EventLoop<T>,
+        dim: WindowDim,
+        title: &str,
+        win_opt: WindowOpt,
+        shared: Option<&WinSurface>,
+        srgb: bool
     ) -> Result<Self, WinError> {
         let win_builder = WindowBuilder::new().with_title(title);
         let win_builder = match dim {
@@ -94,19 +109,27 @@ impl WinSurface {
                     )
             }
         };
-        let win_ctx = ContextBuilder::new()
+        let ctx_builder = ContextBuilder::new()
             .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
             .with_gl_profile(GlProfile::Core)
             .with_multisampling(win_opt.num_samples().unwrap_or(0) as u16)
-            .with_double_buffer(Some(true))
-            .build_windowed(win_builder, &el)
-            .unwrap();
+            .with_depth_buffer(24)
+            .with_srgb(srgb)
+            .with_double_buffer(Some(true));
+        let win_ctx = match shared.map(|surface| &surface.win_ctx) {
+            None => ctx_builder.build_windowed(win_builder, &el)?,
+            Some(CtxCurrWrapper::PossiblyCurrent(sctx)) =>
+                ctx_builder.with_shared_lists(sctx.context()).build_windowed(win_builder, &el)?,
+            Some(CtxCurrWrapper::NotCurrent(sctx)) =>
+                ctx_builder.with_shared_lists(sctx.context()).build_windowed(win_builder, &el)?
+        };
         let win_ctx = unsafe { win_ctx.make_current().map_err(|(_, e)| e)? };
         match win_opt.cursor_mode() {
             CursorMode::Visible => win_ctx.window().set_cursor_visible(true),
             CursorMode::Invisible | CursorMode::Disabled => win_ctx.window().set_cursor_visible(false)
         }
         gl::load_with(|s| win_ctx.get_proc_address(s) as *const c_void);
+        if srgb { unsafe { gl::Enable(gl::FRAMEBUFFER_SRGB); } }
         win_ctx.window().set_visible(true);
         //
         //
@@ -123,6 +146,7 @@ impl WinSurface {
         //
         Ok(WinSurface {
             win_ctx: CtxCurrWrapper::PossiblyCurrent(win_ctx),
+            srgb,
             //gfx_state: Rc::new(RefCell::new(gfx_state))
             gfx_state
         })
@@ -130,11 +154,41 @@ impl WinSurface {
 
     pub fn ctx(&mut self) -> &mut CtxCurrWrapper { &mut self.win_ctx }
 
+    pub fn is_srgb(&self) -> bool { self.srgb }
+
+    pub fn size(&self) -> [u32; 2] {
+        let (w, h) = match &self.win_ctx {
+            CtxCurrWrapper::PossiblyCurrent(ctx) => ctx.window().inner_size().into(),
+            CtxCurrWrapper::NotCurrent(ctx) => ctx.window().inner_size().into()
+        };
+        [w, h]
+    }
+
+    /// Physical pixels per logical pixel.
+    pub fn scale_factor(&self) -> f64 {
+        match &self.win_ctx {
+            CtxCurrWrapper::PossiblyCurrent(ctx) => ctx.window().scale_factor(),
+            CtxCurrWrapper::NotCurrent(ctx) => ctx.window().scale_factor()
+        }
+    }
+
+    /// Inner rect of the window on the screen (x, y, width, height), if the
+    /// platform gives the window position.
+    pub fn screen_rect(&self) -> Option<[f64; 4]> {
+        let window = match &self.win_ctx {
+            CtxCurrWrapper::PossiblyCurrent(ctx) => ctx.window(),
+            CtxCurrWrapper::NotCurrent(ctx) => ctx.window()
+        };
+        let pos = window.inner_position().ok()?;
+        let [w, h] = self.size();
+        Some([pos.x as f64, pos.y as f64, w as f64, h as f64])
+    }
+
     pub fn back_buffer(&mut self) -> Result<Framebuffer<Flat, Dim2, (), ()>, WinError> {
         match &self.win_ctx {
-            CtxCurrWrapper::PossiblyCurrent(ctx) => {
-                let (w, h) = ctx.window().inner_size().into();
-                Ok(Framebuffer::back_buffer(self, [w, h]))
+            CtxCurrWrapper::PossiblyCurrent(_) => {
+                let size = self.size();
+                Ok(Framebuffer::back_buffer(self, size))
             }
             CtxCurrWrapper::NotCurrent(_) =>
                 Err(WinError::WinInternError("using back buffer of not current ctx"))
@@ -155,6 +209,7 @@ pub enum CtxCurrWrapper {
 
 pub struct WinManager {
     current: Option<WindowId>,
+    srgb: bool,
     //gfx_state: Option<Rc<RefCell<GraphicsState>>>,
     others: HashMap<WindowId, Takeable<WinSurface>>
 }
@@ -170,6 +225,7 @@ impl WinManager {
         //let gfx_state = GraphicsState::new().map_err(WinError::GraphicsStateError)?;
         Ok(WinManager {
             current: None,
+            srgb: false,
             //gfx_state: None,
             others: HashMap::default()
         })
@@ -195,6 +251,9 @@ impl WinManager {
     }
     */
 
+    /// Ask an sRGB framebuffer for the windows created afterwards.
+    pub fn set_srgb(&mut self, srgb: bool) { self.srgb = srgb; }
+
     pub fn insert_window(&mut self, surface: WinSurface) -> Result<WindowId, WinError> {
         match &surface.win_ctx {
             CtxCurrWrapper::PossiblyCurrent(ctx) => {
@@ -208,8 +267,8 @@ impl WinManager {
                         }
                         *old_curr_surf = Takeable::new(old_win);
                     }
-                    self.current = Some(id);
                 }
+                self.current = Some(id);
                 self.others.insert(id, Takeable::new(surface));
                 Ok(id)
             }
@@ -218,6 +277,22 @@ impl WinManager {
         }
     }
 
+    /// Create a new window sharing its GL objects with the already managed
+    /// ones, and make it the current one.
+    pub fn create_window<T>(
+        &mut self,
+        el: &EventLoop<T>,
+        dim: WindowDim,
+        title: &str,
+        win_opt: WindowOpt
+    ) -> Result<WindowId, WinError> {
+        let surface = {
+            let shared = self.others.values().next().map(|surface| &**surface);
+            WinSurface::new_shared(el, dim, title, win_opt, shared, self.srgb)?
+        };
+        self.insert_window(surface)
+    }
+
     pub fn remove_window(&mut self, id: WindowId) {
         if Some(id) == self.current { self.current.take(); }
         self.others.remove(&id);
@@ -225,6 +300,14 @@ impl WinManager {
 
     pub fn len(&mut self) -> usize { self.others.len() }
 
+    /// Screen rects of the windows placed on the screen, see
+    /// `WinSurface::screen_rect`.
+    pub fn screen_rects(&self) -> Vec<(WindowId, [f64; 4])> {
+        self.others.iter()
+            .filter_map(|(id, surface)| surface.screen_rect().map(|rect| (*id, rect)))
+            .collect()
+    }
+
     pub fn get_current(&mut self, id: WindowId) -> Result<&mut WinSurface, ContextError> {
         let res = if Some(id) != self.current {
             let ncurr_ref = self.others.get_mut(&id).unwrap();